@@ -0,0 +1,557 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use glob::{MatchOptions, Pattern};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Method, Response, StatusCode};
+use serde_derive::Serialize;
+
+use crate::client::GitlabClient;
+use crate::config::cache_dir;
+use crate::error::{check_status, GlcError};
+use crate::format::format_bytes;
+
+// Times a download that breaks off is picked up again before giving up
+const MAX_ATTEMPTS: usize = 5;
+
+// Bytes between updates of the progress line
+const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Downloads the artifacts archive of a job to glc's cache directory,
+/// streaming it to disk rather than holding it in memory. What was
+/// downloaded before an interruption, in this run or an earlier one, is
+/// kept and only the rest is asked for.
+pub async fn download_artifacts(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+) -> Result<PathBuf> {
+    // Per instance, as IDs are only unique within one. The cache directory
+    // is the user's own, unlike the shared temp directory.
+    let host: String = client
+        .host()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let dir = cache_dir()
+        .ok_or_else(|| anyhow!("no cache directory to download to, set XDG_CACHE_HOME or HOME"))?
+        .join("artifacts")
+        .join(host);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}-{}.zip", project, job));
+    let partial = path.with_extension("zip.part");
+    // Left from an earlier run that couldn't remove it. A job's artifacts
+    // don't change once it's done.
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let mut attempt = 1;
+    loop {
+        match download_to(client, project, job, &partial).await {
+            Ok(()) => break,
+            Err(e) if attempt < MAX_ATTEMPTS && is_interruption(&e) => {
+                eprintln!("\nDownload interrupted ({}), resuming...", e);
+                attempt += 1;
+            }
+            Err(e) => {
+                // Off the progress line
+                if is_interruption(&e) {
+                    eprintln!();
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+// Errors from a connection that broke off, rather than from the server
+fn is_interruption(error: &anyhow::Error) -> bool {
+    if let Some(e) = error.downcast_ref::<io::Error>() {
+        return e.kind() == io::ErrorKind::UnexpectedEof;
+    }
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_body() || e.is_request() || e.is_timeout())
+}
+
+// Appends to `partial` whatever it is still missing of the archive
+async fn download_to(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+    partial: &Path,
+) -> Result<()> {
+    let mut offset = fs::metadata(partial).map_or(0, |m| m.len());
+
+    let url = client.api_url(&format!("projects/{}/jobs/{}/artifacts", project, job))?;
+    let mut response = loop {
+        let mut request = client.request(Method::GET, url.clone());
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = client.send(request).await?;
+        // The earlier download had already got all of it
+        if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(());
+        }
+        let response = check_status(response).await?;
+        // Resumed somewhere else than asked, so start over from the beginning
+        if offset > 0
+            && response.status() == StatusCode::PARTIAL_CONTENT
+            && content_range_start(&response) != Some(offset)
+        {
+            offset = 0;
+            continue;
+        }
+        break response;
+    };
+
+    // Servers that ignore the Range header send the whole archive again
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial)?;
+
+    let done = if resumed { offset } else { 0 };
+    let total = response.content_length().map(|len| len + done);
+    if resumed {
+        eprintln!(
+            "Resuming download at {}",
+            format_bytes(done as usize).trim()
+        );
+    }
+
+    save(&mut response, &mut file, "artifacts", done, total).await
+}
+
+// Writes the rest of the body to `file`, after the `done` bytes already
+// in it, showing how far along it is
+async fn save(
+    response: &mut Response,
+    file: &mut File,
+    what: &str,
+    mut done: u64,
+    total: Option<u64>,
+) -> Result<()> {
+    let mut shown = done;
+    progress(what, done, total);
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        done += chunk.len() as u64;
+        if done - shown >= PROGRESS_STEP {
+            progress(what, done, total);
+            shown = done;
+        }
+    }
+    progress(what, done, total);
+    file.sync_all()?;
+    eprintln!();
+
+    if total.is_some_and(|total| done < total) {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+// Start of "Content-Range: bytes <start>-<end>/<size>"
+fn content_range_start(response: &Response) -> Option<u64> {
+    let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    range
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn progress(what: &str, done: u64, total: Option<u64>) {
+    let done_bytes = format_bytes(done as usize);
+    match total {
+        Some(total) if total > 0 => eprint!(
+            "\rDownloading {}: {} of {} ({:3}%)",
+            what,
+            done_bytes,
+            format_bytes(total as usize).trim(),
+            done * 100 / total
+        ),
+        _ => eprint!("\rDownloading {}: {}", what, done_bytes),
+    }
+    io::stderr().flush().ok();
+}
+
+/// Downloads a single file out of a job's artifacts to `path`, which
+/// GitLab extracts on its end.
+pub async fn download_artifact_file(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+    name: &str,
+    path: &Path,
+) -> Result<()> {
+    let name_path: Vec<String> = name
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect();
+    let mut response = client
+        .get(&format!(
+            "projects/{}/jobs/{}/artifacts/{}",
+            project,
+            job,
+            name_path.join("/")
+        ))
+        .await?;
+
+    let mut file = File::create(path)?;
+    let total = response.content_length();
+    save(&mut response, &mut file, name, 0, total).await
+}
+
+/// A glob over the paths in an artifacts archive, where `*` stays within
+/// a directory and `**` crosses them
+pub struct ArtifactGlob(Pattern);
+
+impl ArtifactGlob {
+    pub fn new(pattern: &str) -> Result<ArtifactGlob, GlcError> {
+        Pattern::new(pattern)
+            .map(ArtifactGlob)
+            .map_err(|e| GlcError::Config(format!("invalid pattern {:?}: {}", pattern, e)))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.0.matches_with(name, options)
+    }
+}
+
+/// A file in an artifacts archive, as its central directory lists it
+#[derive(Serialize)]
+pub struct ArtifactEntry {
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+}
+
+// The end of central directory record is 22 bytes plus a comment of at
+// most 64 KiB, and is preceded by the 20 byte zip64 locator
+const TAIL_SIZE: u64 = 22 + 0xffff + 20;
+
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+// The bytes of `range` ("100-199", or "-100" for the last 100) of the
+// artifacts archive and the archive's full size, or None if the server
+// doesn't do ranges.
+async fn get_range(
+    client: &GitlabClient,
+    path: &str,
+    range: &str,
+) -> Result<Option<(Vec<u8>, u64)>> {
+    let url = client.api_url(path)?;
+    let request = client
+        .request(Method::GET, url)
+        .header(RANGE, format!("bytes={}", range));
+    let response = check_status(client.send(request).await?).await?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Ok(None);
+    }
+    // "bytes <start>-<end>/<size>"
+    let size = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|r| r.to_str().ok())
+        .and_then(|r| r.rsplit_once('/'))
+        .and_then(|(_, size)| size.parse().ok());
+    let Some(size) = size else {
+        return Ok(None);
+    };
+    Ok(Some((response.bytes().await?.to_vec(), size)))
+}
+
+/// Lists the files in a job's artifacts archive by reading only its
+/// central directory, using range requests. None if the server can't
+/// serve ranges of it.
+pub async fn list_artifacts(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+) -> Result<Option<Vec<ArtifactEntry>>> {
+    let path = format!("projects/{}/jobs/{}/artifacts", project, job);
+    let in_job = |e: anyhow::Error| anyhow!("artifacts of job {}: {}", job, e);
+
+    let Some((tail, size)) = get_range(client, &path, &format!("-{}", TAIL_SIZE)).await? else {
+        return Ok(None);
+    };
+    let tail_start = size - tail.len() as u64;
+
+    let (mut end, zip64_start) = find_directory_end(&tail).map_err(in_job)?;
+    if let Some(start) = zip64_start {
+        let range = format!("{}-{}", start, start + ZIP64_EOCD_SIZE - 1);
+        let (record, _) = get_range(client, &path, &range)
+            .await?
+            .ok_or_else(|| in_job(malformed()))?;
+        end = parse_zip64_end(&record).map_err(in_job)?;
+    }
+
+    let dir = if end.start >= tail_start {
+        let start = (end.start - tail_start) as usize;
+        tail.get(start..start + end.size as usize)
+            .ok_or_else(|| in_job(malformed()))?
+            .to_vec()
+    } else if end.size == 0 {
+        Vec::new()
+    } else {
+        let range = format!("{}-{}", end.start, end.start + end.size - 1);
+        get_range(client, &path, &range)
+            .await?
+            .ok_or_else(|| in_job(malformed()))?
+            .0
+    };
+    parse_central_directory(&dir, end.count)
+        .map(Some)
+        .map_err(in_job)
+}
+
+fn malformed() -> anyhow::Error {
+    anyhow!("malformed zip archive")
+}
+
+// Where the central directory is, and how many files it lists
+#[derive(Debug, PartialEq)]
+struct DirectoryEnd {
+    count: u64,
+    size: u64,
+    start: u64,
+}
+
+// The zip64 end of central directory record, up to the fields read here
+const ZIP64_EOCD_SIZE: u64 = 56;
+
+// Finds the end of central directory record in the last bytes of an
+// archive. Archives over 4 GiB or 65535 files keep the real numbers in a
+// zip64 record instead, whose offset is returned along with it.
+fn find_directory_end(tail: &[u8]) -> Result<(DirectoryEnd, Option<u64>)> {
+    // The comment after it could hold the signature too, but then the
+    // comment length wouldn't line up with the end of the archive
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| {
+            u32_at(tail, i) == Some(EOCD_SIGNATURE)
+                && u16_at(tail, i + 20).is_some_and(|len| i + 22 + len as usize == tail.len())
+        })
+        .ok_or_else(|| anyhow!("not a zip archive"))?;
+    let end = DirectoryEnd {
+        count: u16_at(tail, eocd + 10).ok_or_else(malformed)? as u64,
+        size: u32_at(tail, eocd + 12).ok_or_else(malformed)? as u64,
+        start: u32_at(tail, eocd + 16).ok_or_else(malformed)? as u64,
+    };
+    let zip64_start = if eocd >= 20 && u32_at(tail, eocd - 20) == Some(ZIP64_LOCATOR_SIGNATURE) {
+        Some(u64_at(tail, eocd - 12).ok_or_else(malformed)?)
+    } else {
+        None
+    };
+    Ok((end, zip64_start))
+}
+
+fn parse_zip64_end(record: &[u8]) -> Result<DirectoryEnd> {
+    if u32_at(record, 0) != Some(ZIP64_EOCD_SIGNATURE) {
+        return Err(malformed());
+    }
+    Ok(DirectoryEnd {
+        count: u64_at(record, 32).ok_or_else(malformed)?,
+        size: u64_at(record, 40).ok_or_else(malformed)?,
+        start: u64_at(record, 48).ok_or_else(malformed)?,
+    })
+}
+
+fn parse_central_directory(dir: &[u8], count: u64) -> Result<Vec<ArtifactEntry>> {
+    let mut entries = Vec::new();
+    let mut at = 0;
+    for _ in 0..count {
+        if u32_at(dir, at) != Some(CENTRAL_HEADER_SIGNATURE) {
+            return Err(malformed());
+        }
+        let mut compressed_size = u32_at(dir, at + 20).ok_or_else(malformed)? as u64;
+        let mut size = u32_at(dir, at + 24).ok_or_else(malformed)? as u64;
+        let name_len = u16_at(dir, at + 28).ok_or_else(malformed)? as usize;
+        let extra_len = u16_at(dir, at + 30).ok_or_else(malformed)? as usize;
+        let comment_len = u16_at(dir, at + 32).ok_or_else(malformed)? as usize;
+
+        let name_start = at + 46;
+        let name = dir
+            .get(name_start..name_start + name_len)
+            .ok_or_else(malformed)?;
+        let extra = dir
+            .get(name_start + name_len..name_start + name_len + extra_len)
+            .ok_or_else(malformed)?;
+
+        // The zip64 extra field has the sizes that didn't fit, in order
+        let mut i = 0;
+        while let (Some(id), Some(len)) = (u16_at(extra, i), u16_at(extra, i + 2)) {
+            if id == 0x0001 {
+                let mut value = i + 4;
+                if size == 0xffffffff {
+                    size = u64_at(extra, value).ok_or_else(malformed)?;
+                    value += 8;
+                }
+                if compressed_size == 0xffffffff {
+                    compressed_size = u64_at(extra, value).ok_or_else(malformed)?;
+                }
+            }
+            i += 4 + len as usize;
+        }
+
+        entries.push(ArtifactEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            size,
+            compressed_size,
+        });
+        at = name_start + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+    use super::*;
+
+    fn zip(files: &[(&str, &[u8])], comment: &str, options: FileOptions) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.set_comment(comment);
+        writer.finish().unwrap().into_inner()
+    }
+
+    // What list_artifacts does, with the whole archive at hand
+    fn list(archive: &[u8]) -> Vec<ArtifactEntry> {
+        let tail_start = archive.len().saturating_sub(TAIL_SIZE as usize);
+        let (mut end, zip64_start) = find_directory_end(&archive[tail_start..]).unwrap();
+        if let Some(start) = zip64_start {
+            end = parse_zip64_end(&archive[start as usize..]).unwrap();
+        }
+        let dir = &archive[end.start as usize..(end.start + end.size) as usize];
+        parse_central_directory(dir, end.count).unwrap()
+    }
+
+    #[test]
+    fn lists_files_with_sizes() {
+        let log = b"line\n".repeat(1000);
+        let archive = zip(
+            &[("build/out.log", &log), ("empty", b"")],
+            "",
+            FileOptions::default(),
+        );
+        let entries = list(&archive);
+
+        let mut expected = ZipArchive::new(Cursor::new(&archive)).unwrap();
+        assert_eq!(entries.len(), 2);
+        for (i, entry) in entries.iter().enumerate() {
+            let file = expected.by_index(i).unwrap();
+            assert_eq!(entry.name, file.name());
+            assert_eq!(entry.size, file.size());
+            assert_eq!(entry.compressed_size, file.compressed_size());
+        }
+        assert_eq!(entries[0].size, 5000);
+        assert!(entries[0].compressed_size < 5000);
+    }
+
+    #[test]
+    fn skips_signature_in_comment() {
+        let archive = zip(
+            &[("a.txt", b"a")],
+            "trailing PK\u{5}\u{6} comment",
+            FileOptions::default(),
+        );
+        let entries = list(&archive);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "a.txt");
+    }
+
+    #[test]
+    fn reads_zip64_end_record() {
+        let names: Vec<String> = (0..70_000).map(|i| format!("f/{}", i)).collect();
+        let files: Vec<(&str, &[u8])> = names.iter().map(|n| (n.as_str(), &b""[..])).collect();
+        // Stored, as compressing that many files takes a while
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let archive = zip(&files, "", stored);
+
+        let tail_start = archive.len() - TAIL_SIZE as usize;
+        let (end, zip64_start) = find_directory_end(&archive[tail_start..]).unwrap();
+        assert_eq!(end.count, 0xffff);
+        assert!(zip64_start.is_some());
+
+        let entries = list(&archive);
+        assert_eq!(entries.len(), 70_000);
+        assert_eq!(entries[69_999].name, "f/69999");
+    }
+
+    #[test]
+    fn reads_zip64_sizes() {
+        let size: u64 = 6 << 30;
+        let compressed_size: u64 = 5 << 30;
+        let mut header = Vec::new();
+        header.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        header.extend([0; 16]);
+        header.extend(0xffffffffu32.to_le_bytes()); // compressed size
+        header.extend(0xffffffffu32.to_le_bytes()); // size
+        header.extend(7u16.to_le_bytes()); // name length
+        header.extend(20u16.to_le_bytes()); // extra field length
+        header.extend(0u16.to_le_bytes()); // comment length
+        header.extend([0; 12]);
+        header.extend(b"big.bin");
+        header.extend(1u16.to_le_bytes());
+        header.extend(16u16.to_le_bytes());
+        header.extend(size.to_le_bytes());
+        header.extend(compressed_size.to_le_bytes());
+
+        let entries = parse_central_directory(&header, 1).unwrap();
+        assert_eq!(entries[0].name, "big.bin");
+        assert_eq!(entries[0].size, size);
+        assert_eq!(entries[0].compressed_size, compressed_size);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(find_directory_end(b"<html>not found</html>").is_err());
+        assert!(parse_central_directory(b"PK\x03\x04", 1).is_err());
+    }
+}
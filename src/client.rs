@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::credentials::Credentials;
+use crate::error::{check_status, GlcError};
+
+// Number of times a request is retried before giving up
+const MAX_RETRIES: u32 = 5;
+// First backoff delay, doubled for every retry
+const BASE_DELAY: Duration = Duration::from_millis(500);
+// Upper bound for any single wait, including server-requested ones
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Shared GitLab API client. Owns the server URL, the token and the
+/// underlying connection pool, so it is cheap to clone and should be
+/// created once and handed to everything that talks to the API.
+#[derive(Clone)]
+pub struct GitlabClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl GitlabClient {
+    pub fn new(creds: &Credentials) -> GitlabClient {
+        GitlabClient {
+            client: reqwest::Client::new(),
+            base_url: creds.url.trim_end_matches('/').to_string(),
+            token: creds.token.clone(),
+        }
+    }
+
+    /// Host (and port) of the GitLab instance
+    pub fn host(&self) -> &str {
+        let host = self
+            .base_url
+            .split_once("://")
+            .map_or(&*self.base_url, |(_, h)| h);
+        host.split('/').next().unwrap_or(host)
+    }
+
+    /// Builds a full API URL from a path relative to `/api/v4/`,
+    /// e.g. `projects/42/jobs?per_page=100`.
+    pub fn api_url(&self, path: &str) -> Result<Url> {
+        Ok(Url::parse(&format!(
+            "{}/api/v4/{}",
+            self.base_url,
+            path.trim_start_matches('/')
+        ))?)
+    }
+
+    pub fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.client.request(method, url).bearer_auth(&self.token)
+    }
+
+    /// GET request, failing with a `GlcError` on any non-2xx status.
+    pub async fn get(&self, path: &str) -> Result<Response> {
+        let url = self.api_url(path)?;
+        let response = self.send(self.request(Method::GET, url)).await?;
+        Ok(check_status(response).await?)
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        parse_json(self.get(path).await?).await
+    }
+
+    /// POST request, failing with a `GlcError` on any non-2xx status.
+    pub async fn post(&self, path: &str) -> Result<Response> {
+        let url = self.api_url(path)?;
+        let response = self.send(self.request(Method::POST, url)).await?;
+        Ok(check_status(response).await?)
+    }
+
+    /// DELETE request, failing with a `GlcError` on any non-2xx status.
+    pub async fn delete(&self, path: &str) -> Result<Response> {
+        let url = self.api_url(path)?;
+        let response = self.send(self.request(Method::DELETE, url)).await?;
+        Ok(check_status(response).await?)
+    }
+
+    /// POST request with a JSON body, failing with a `GlcError` on any
+    /// non-2xx status.
+    pub async fn post_json<B: Serialize>(&self, path: &str, body: &B) -> Result<Response> {
+        let url = self.api_url(path)?;
+        let response = self
+            .send(self.request(Method::POST, url).json(body))
+            .await?;
+        Ok(check_status(response).await?)
+    }
+
+    /// Sends a request, retrying with exponential backoff.
+    ///
+    /// Idempotent requests are retried on connection errors and on
+    /// transient server errors (502/503/504). Every request, including
+    /// POSTs, is retried when rate limited (429), since GitLab rejects
+    /// those before acting on them. `Retry-After` and `RateLimit-Reset`
+    /// take precedence over the computed backoff.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| is_idempotent(r.method()));
+        let mut attempt = 0;
+
+        loop {
+            // Streaming bodies can't be replayed, so send those only once
+            let req = match request.try_clone() {
+                Some(req) if attempt < MAX_RETRIES => req,
+                _ => return Ok(request.send().await?),
+            };
+
+            let delay = match req.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status != StatusCode::TOO_MANY_REQUESTS
+                        && !(idempotent && is_transient(status))
+                    {
+                        return Ok(response);
+                    }
+                    let delay = server_delay(response.headers()).unwrap_or(backoff(attempt));
+                    eprintln!(
+                        "{} returned {}, retrying in {:.1}s",
+                        response.url().path(),
+                        status,
+                        delay.as_secs_f64()
+                    );
+                    delay
+                }
+                Err(e) if idempotent && (e.is_connect() || e.is_timeout()) => {
+                    let delay = backoff(attempt);
+                    eprintln!("{}, retrying in {:.1}s", e, delay.as_secs_f64());
+                    delay
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Deserializes a response body, reporting mismatches against our types
+/// as `GlcError::Schema` along with the endpoint that sent them.
+pub async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let path = response.url().path().to_string();
+    let text = response.text().await?;
+
+    serde_json::from_str(&text)
+        .map_err(|e| GlcError::Schema(format!("{} in response from {}", e, path)).into())
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BASE_DELAY
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_DELAY)
+}
+
+// How long the server asked us to wait, if it told us
+fn server_delay(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let delay = if let Some(retry_after) = header(RETRY_AFTER.as_str()) {
+        // Either delta-seconds or an HTTP date
+        match retry_after.trim().parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => until(DateTime::parse_from_rfc2822(retry_after).ok()?.into()),
+        }
+    } else {
+        // GitLab sends the reset time as a unix timestamp
+        let reset = header("ratelimit-reset")?.trim().parse::<i64>().ok()?;
+        until(DateTime::from_timestamp(reset, 0)?)
+    };
+
+    Some(delay.min(MAX_DELAY))
+}
+
+fn until(when: DateTime<Utc>) -> Duration {
+    (when - Utc::now()).to_std().unwrap_or(Duration::ZERO)
+}
@@ -1,31 +1,27 @@
-use reqwest::Url;
-use std::sync::Arc;
-
 use futures::future::join_all;
 
-use anyhow::Result;
-
-use crate::credentials::Credentials;
+use crate::client::GitlabClient;
+use crate::error::GlcError;
 use crate::job::{find_jobs, get_job_details, Job};
 
 pub async fn cancel_job(
-    creds: &Credentials,
+    client: &GitlabClient,
     project: &str,
     jobs: Option<Vec<usize>>,
     pipeline: Option<usize>,
     job_names: Option<Vec<String>>,
-) -> Result<(), anyhow::Error> {
+) -> Result<(), GlcError> {
     let job_names: Option<Vec<String>> = job_names.clone();
     let job_names: Option<Vec<&str>> = job_names
         .as_ref()
         .map(|vec| vec.iter().map(AsRef::as_ref).collect());
     let jobs: Vec<Job> = if let Some(pipeline) = pipeline {
-        find_jobs(creds, project, vec![pipeline], job_names, None, None).await?
+        find_jobs(client, project, vec![pipeline], job_names, None, None).await?
     } else {
         let futures = jobs
             .unwrap()
             .into_iter()
-            .map(|j| get_job_details(Arc::new(creds.clone()), project.to_string(), j));
+            .map(|j| get_job_details(client, project, j));
 
         let results = join_all(futures).await;
         results.into_iter().collect::<anyhow::Result<Vec<Job>>>()?
@@ -35,14 +31,9 @@ pub async fn cancel_job(
     println!("Cancelling {} jobs...", jobs.len());
 
     for job in jobs {
-        let url = format!(
-            "{}/api/v4/projects/{}/jobs/{}/cancel",
-            creds.url, project, job
-        );
-        let url = Url::parse(&url)?;
-
-        let client = reqwest::Client::new();
-        let response = client.post(url).bearer_auth(&creds.token).send().await?;
+        let response = client
+            .post(&format!("projects/{}/jobs/{}/cancel", project, job))
+            .await?;
 
         let ret = response.text().await?;
         println!("Job {} ret: {}", job, ret);
@@ -0,0 +1,91 @@
+use std::fs;
+
+use prettytable::Cell;
+use serde_derive::Serialize;
+
+use crate::client::{parse_json, GitlabClient};
+use crate::commands::wait::wait;
+use crate::error::GlcError;
+use crate::format::format_status;
+use crate::git::current_branch;
+use crate::output::{Column, Output};
+use crate::pipeline::Pipeline;
+
+#[derive(Serialize)]
+struct PipelineVariable {
+    key: String,
+    value: String,
+    variable_type: &'static str,
+}
+
+#[derive(Serialize)]
+struct CreateRequest {
+    #[serde(rename = "ref")]
+    rref: String,
+    variables: Vec<PipelineVariable>,
+}
+
+pub async fn create_pipeline(
+    client: &GitlabClient,
+    project: &str,
+    rref: Option<String>,
+    variables: Vec<(String, String)>,
+    file_variables: Vec<(String, String)>,
+    watch: bool,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let rref = rref.or_else(current_branch).ok_or_else(|| {
+        GlcError::Config("no ref given and no branch checked out, use --ref".to_string())
+    })?;
+
+    let mut request = CreateRequest {
+        rref,
+        variables: variables
+            .into_iter()
+            .map(|(key, value)| PipelineVariable {
+                key,
+                value,
+                variable_type: "env_var",
+            })
+            .collect(),
+    };
+    // File variables are given as paths, GitLab wants the contents
+    for (key, path) in file_variables {
+        let value = fs::read_to_string(&path)
+            .map_err(|e| GlcError::Config(format!("failed to read {} for {}: {}", path, key, e)))?;
+        request.variables.push(PipelineVariable {
+            key,
+            value,
+            variable_type: "file",
+        });
+    }
+
+    let response = client
+        .post_json(&format!("projects/{}/pipeline", project), &request)
+        .await?;
+    let pipeline: Pipeline = parse_json(response).await?;
+
+    let columns = vec![
+        Column::new("ID", |p: &Pipeline| p.id.to_string()),
+        Column::new("Ref", |p: &Pipeline| p.rref.clone()),
+        Column::new("Status", |p: &Pipeline| p.status.clone())
+            .human(|p: &Pipeline| Cell::new(&format_status(&p.status).to_string())),
+        Column::new("URL", |p: &Pipeline| p.web_url.clone()),
+    ];
+    output.print(std::slice::from_ref(&pipeline), &columns)?;
+
+    if watch {
+        wait(
+            client,
+            project,
+            None,
+            Some(pipeline.id as usize),
+            None,
+            None,
+            None,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
@@ -1,76 +1,181 @@
-use std::ffi::OsStr;
-use std::fs::File;
-use std::io::{self, Cursor};
-use std::path::Path;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::Result;
-use reqwest::StatusCode;
 use zip::read::ZipArchive;
-use zip::result::ZipError;
 
-use crate::credentials::Credentials;
+use crate::artifacts::{download_artifact_file, download_artifacts, list_artifacts, ArtifactGlob};
+use crate::client::GitlabClient;
+use crate::error::GlcError;
 
-pub async fn get_artifact(
-    credentials: &Credentials,
+/// Downloads the artifacts archive of a job and opens it.
+pub async fn get_artifacts_archive(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+) -> Result<ZipArchive<File>, GlcError> {
+    let path = download_artifacts(client, project, job).await?;
+    let archive = ZipArchive::new(File::open(&path)?)?;
+    // Reading goes on through the open file. Where open files can't be
+    // removed, it stays in the cache directory and is used again the next
+    // time this job's artifacts are needed.
+    fs::remove_file(&path).ok();
+    Ok(archive)
+}
+
+// Checks them all up front rather than stopping halfway through
+fn check_overwrite<'a>(paths: impl Iterator<Item = &'a Path>, force: bool) -> Result<(), GlcError> {
+    let existing: Vec<String> = paths
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+    if !force && !existing.is_empty() {
+        return Err(GlcError::Config(format!(
+            "not overwriting {} without --force",
+            existing.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+// Fetches the files at exactly these paths in the artifacts, one at a time
+async fn get_artifact_files(
+    client: &GitlabClient,
     project: &str,
     job: usize,
-    artifact: String,
-) -> Result<()> {
-    let client = reqwest::Client::new();
-
-    let url = format!(
-        "{}/api/v4/projects/{}/jobs/{}/artifacts",
-        credentials.url, project, job
-    );
-
-    let response = client
-        .get(&url)
-        .bearer_auth(&credentials.token)
-        .send()
-        .await?;
-
-    // Check that we didn't receive an HTTP error status
-    if response.status() != StatusCode::OK {
-        println!("{:#?}", response);
-        return Err(anyhow::anyhow!(
-            "Received a non-OK HTTP status: {}",
-            response.status()
-        ));
+    names: &[String],
+    output_dir: &Path,
+    force: bool,
+) -> Result<(), GlcError> {
+    let mut paths = Vec::new();
+    for name in names {
+        let name = name.trim_start_matches("./");
+        // Same as enclosed_name() for the archive
+        if !Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(GlcError::Config(format!(
+                "refusing to extract {}, it points outside of the output directory",
+                name
+            )));
+        }
+        paths.push((name, output_dir.join(name)));
     }
+    check_overwrite(paths.iter().map(|(_, path)| path.as_path()), force)?;
 
-    // Get the response body
-    let bytes = response.bytes().await?;
-
-    let reader = Cursor::new(bytes);
-    let mut archive = ZipArchive::new(reader)?;
-    let names = archive
-        .file_names()
-        .map(|s| s.to_string())
-        .collect::<Vec<String>>();
-
-    match archive.by_name(artifact.as_str()) {
-        Err(ZipError::FileNotFound) => {
-            println!("File {} not found. Available files:", artifact);
-            for f in names {
-                println!("    {}", f);
+    for (name, path) in paths {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        match download_artifact_file(client, project, job, name, &path).await {
+            Err(e) => match GlcError::from(e) {
+                GlcError::NotFound(_) => {
+                    let available = list_artifacts(client, project, job).await.ok().flatten();
+                    if let Some(entries) = available {
+                        eprintln!("File {} not found. Available files:", name);
+                        for entry in entries {
+                            eprintln!("    {}", entry.name);
+                        }
+                    }
+                    return Err(GlcError::NotFound(format!(
+                        "{} in the artifacts of job {}",
+                        name, job
+                    )));
+                }
+                e => return Err(e),
+            },
+            Ok(()) => {
+                let file_size = fs::metadata(&path)?.len();
+                println!("Extracted {} bytes to {}", file_size, path.display());
             }
         }
-        Err(e) => {
-            return Err(anyhow::anyhow!("Received ZipError: {}", e));
+    }
+    Ok(())
+}
+
+// Where each file in the archive matching `patterns` goes under `output_dir`
+fn extract_paths(
+    archive: &mut ZipArchive<File>,
+    patterns: &[ArtifactGlob],
+    output_dir: &Path,
+) -> Result<Vec<(usize, PathBuf)>, GlcError> {
+    let mut paths = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        if !patterns.is_empty() && !patterns.iter().any(|p| p.matches(file.name())) {
+            continue;
         }
-        Ok(mut file) => {
-            let path = Path::new(&artifact);
-            let filename: &OsStr = path.file_name().unwrap();
-            // Open a file to write the artifact to
-            let mut out = File::create(filename)?;
-            io::copy(&mut file, &mut out)?;
+        // Absolute paths or ones climbing out with ".." would write
+        // outside of the output directory
+        let Some(name) = file.enclosed_name() else {
+            return Err(GlcError::Config(format!(
+                "refusing to extract {}, it points outside of the output directory",
+                file.name()
+            )));
+        };
+        paths.push((i, output_dir.join(name)));
+    }
+    Ok(paths)
+}
+
+/// Extracts the files matching any of `names` (globs), or all of them,
+/// into `output_dir`, keeping their paths within the archive.
+pub async fn get_artifact(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+    names: Vec<String>,
+    output_dir: PathBuf,
+    force: bool,
+) -> Result<(), GlcError> {
+    let patterns = names
+        .iter()
+        .map(|n| ArtifactGlob::new(n))
+        .collect::<Result<Vec<ArtifactGlob>, GlcError>>()?;
+
+    // Paths without wildcards can be fetched one by one, which saves
+    // downloading the whole archive
+    if !names.is_empty() && names.iter().all(|n| !n.contains(['*', '?', '['])) {
+        return get_artifact_files(client, project, job, &names, &output_dir, force).await;
+    }
 
-            out.sync_all()?;
+    let mut archive = get_artifacts_archive(client, project, job).await?;
+    let paths = extract_paths(&mut archive, &patterns, &output_dir)?;
 
-            let file_size = out.metadata()?.len();
-            println!("Extracted {} bytes to {}", file_size, artifact);
+    if paths.is_empty() {
+        eprintln!(
+            "No files matching {} found. Available files:",
+            names.join(", ")
+        );
+        for f in archive.file_names() {
+            eprintln!("    {}", f);
         }
-    };
+        return Err(GlcError::NotFound(format!(
+            "no matching artifacts in job {}",
+            job
+        )));
+    }
+
+    check_overwrite(paths.iter().map(|(_, path)| path.as_path()), force)?;
+
+    for (i, path) in paths {
+        let mut file = archive.by_index(i)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Open a file to write the artifact to
+        let mut out = File::create(&path)?;
+        io::copy(&mut file, &mut out)?;
+
+        out.sync_all()?;
+
+        let file_size = out.metadata()?.len();
+        println!("Extracted {} bytes to {}", file_size, path.display());
+    }
 
     Ok(())
 }
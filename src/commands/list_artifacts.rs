@@ -0,0 +1,50 @@
+use prettytable::Cell;
+
+use crate::artifacts::{list_artifacts as list_archive, ArtifactEntry};
+use crate::client::GitlabClient;
+use crate::commands::get_artifact::get_artifacts_archive;
+use crate::error::GlcError;
+use crate::format::format_bytes;
+use crate::output::{Column, Output};
+
+fn size_cell(size: u64) -> Cell {
+    Cell::new(&format_bytes(size as usize).to_string()).style_spec("r")
+}
+
+/// Lists the files in a job's artifacts with their sizes. Only the
+/// archive's directory is fetched, unless the server can't serve parts
+/// of it.
+pub async fn list_artifacts(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let entries = match list_archive(client, project, job).await? {
+        Some(entries) => entries,
+        None => {
+            let mut archive = get_artifacts_archive(client, project, job).await?;
+            let mut entries = Vec::new();
+            for i in 0..archive.len() {
+                let file = archive.by_index(i)?;
+                entries.push(ArtifactEntry {
+                    name: file.name().to_string(),
+                    size: file.size(),
+                    compressed_size: file.compressed_size(),
+                });
+            }
+            entries
+        }
+    };
+
+    let columns = vec![
+        Column::new("Name", |e: &ArtifactEntry| e.name.clone()),
+        Column::new("Size", |e: &ArtifactEntry| e.size.to_string())
+            .human(|e: &ArtifactEntry| size_cell(e.size)),
+        Column::new("Compressed", |e: &ArtifactEntry| {
+            e.compressed_size.to_string()
+        })
+        .human(|e: &ArtifactEntry| size_cell(e.compressed_size)),
+    ];
+    output.print(&entries, &columns)
+}
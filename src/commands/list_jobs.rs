@@ -1,13 +1,14 @@
 use std::cmp::Ordering;
 
 use chrono::{DateTime, Utc};
-use colored::*;
-use prettytable::{format, row, Cell, Row, Table};
+use prettytable::{format, Cell, Row, Table};
 
-use crate::credentials::Credentials;
-use crate::format::{format_bytes, format_seconds};
+use crate::client::GitlabClient;
+use crate::error::GlcError;
+use crate::format::{format_bytes, format_seconds, format_status};
 use crate::job::find_jobs;
 use crate::job::Job;
+use crate::output::{select, Column, Output};
 
 fn compare_dates_with_tolerance(a: &DateTime<Utc>, b: &DateTime<Utc>, tolerance: i64) -> Ordering {
     let difference = a.signed_duration_since(*b).num_seconds().abs();
@@ -19,13 +20,54 @@ fn compare_dates_with_tolerance(a: &DateTime<Utc>, b: &DateTime<Utc>, tolerance:
     }
 }
 
+/// Every column a job listing can show
+pub fn job_columns() -> Vec<Column<'static, Job>> {
+    vec![
+        Column::new("ID", |j: &Job| j.id.to_string()),
+        Column::new("Pipeline", |j: &Job| j.pipeline.id.to_string()),
+        Column::new("Ref", |j: &Job| j.pipeline.rref.clone()),
+        Column::new("Status", |j: &Job| j.status.clone())
+            .human(|j: &Job| Cell::new(&format_status(&j.status).to_string())),
+        Column::new("Reason", |j: &Job| {
+            j.failure_reason.clone().unwrap_or_default()
+        }),
+        Column::new("Step", |j: &Job| j.stage.clone()),
+        Column::new("Artifacts", |j: &Job| j.artifacts_size.to_string())
+            .human(|j: &Job| Cell::new(&format_bytes(j.artifacts_size))),
+        Column::new("Name", |j: &Job| j.name.clone()),
+        Column::new("Tags", |j: &Job| {
+            j.tag_list.clone().unwrap_or_default().join(" ")
+        }),
+        Column::new("SHA", |j: &Job| j.pipeline.sha.clone())
+            .human(|j: &Job| Cell::new(&j.pipeline.sha[..14])),
+        Column::new("Source", |j: &Job| j.pipeline.source.clone()),
+        Column::new("Created", |j: &Job| j.created_at.to_rfc3339())
+            .human(|j: &Job| Cell::new(&j.created_at.to_string())),
+        Column::new("Runner", |j: &Job| {
+            j.runner
+                .as_ref()
+                .map_or("<unknown>".to_string(), |r| r.description.clone())
+        }),
+        Column::new("Elapsed", |j: &Job| {
+            j.duration.unwrap_or_default().to_string()
+        })
+        .key("duration")
+        .human(|j: &Job| Cell::new(&format_seconds(j.duration.unwrap_or_default()))),
+        Column::new("Queued", |j: &Job| {
+            j.queued_duration.unwrap_or_default().to_string()
+        })
+        .human(|j: &Job| Cell::new(&format_seconds(j.queued_duration.unwrap_or_default()))),
+    ]
+}
+
 pub async fn list_jobs(
-    creds: &Credentials,
+    client: &GitlabClient,
     project: &str,
     pipelines: Vec<usize>,
     max_age: isize,
     status: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    output: &Output,
+) -> Result<(), GlcError> {
     let list_pipeline = !pipelines.is_empty();
     // With specific pipelines, don't use max_age
     let max_age = if pipelines.is_empty() {
@@ -33,38 +75,21 @@ pub async fn list_jobs(
     } else {
         None
     };
-    let jobs: Vec<Job> = find_jobs(creds, project, pipelines, None, max_age, status).await?;
-
-    // Create a new table
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-    let mut titles = vec![
-        "ID",
-        "Pipeline",
-        "Ref",
-        "Status",
-        "Reason",
-        "Step",
-        "Artifacts",
-        "Name",
-        "Tags",
-        "Runner",
-        "Elapsed",
-        "Queued",
-    ];
-    if list_pipeline {
-        titles.push("Hist");
-    }
-
-    table.set_titles(titles.into_iter().map(|t| Cell::new(t)).collect());
+    let jobs: Vec<Job> = find_jobs(client, project, pipelines, None, max_age, status).await?;
 
-    if jobs.is_empty() {
+    if jobs.is_empty() && output.is_table() {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
         table.add_row(Row::new(vec![Cell::new("No jobs found").with_hspan(12)]));
         table.printstd();
         return Ok(());
     }
     // Normalize jobs based on oldest created_at
-    let min = jobs.iter().map(|job| job.created_at).min().unwrap();
+    let min = jobs
+        .iter()
+        .map(|job| job.created_at)
+        .min()
+        .unwrap_or(Utc::now());
     let max = jobs
         .iter()
         .map(|job| job.finished_at)
@@ -88,61 +113,49 @@ pub async fn list_jobs(
 
     let nr_jobs = jobs.len();
 
-    // Add a row per time
-    for job in jobs.into_iter() {
-        let status = match job.status.as_str() {
-            "success" => "✅\u{00a0} Success".green(),
-            "failed" => "❌\u{00a0} Failed".red(),
-            "running" => "⏳\u{00a0} Running".yellow(),
-            "created" => "🌱\u{00a0} Created".normal(),
-            stat => format!("❓\u{00a0} {stat}").normal(),
-        };
-        let duration = if job.status == "running" {
-            Utc::now() - job.started_at
-        } else {
-            job.finished_at - job.started_at
-        };
-        let start_position = (job.started_at - min).num_seconds() as f64 * scale;
-        let duration_width = duration.num_seconds() as f64 * scale;
-        let duration_width = duration_width.max(1.0).min(30.0);
-        let start_position = start_position as usize;
-        let duration_width = duration_width as usize;
-        let runner = if let Some(runner) = job.runner {
-            runner.description
-        } else {
-            "<unknown>".to_string()
-        };
-        //println!("job {:?} status {:?} started {:?} duration {:?}", job.id, job.status, start_position, duration_width);
-        let mut row = row![
-            &job.id.to_string(),
-            &job.pipeline.id,
-            &job.pipeline.rref,
-            &status.to_string(),
-            &job.failure_reason.unwrap_or_default(),
-            &job.stage,
-            &format_bytes(job.artifacts_size),
-            &job.name,
-            &job.tag_list.unwrap_or_default().join(" "),
-            &runner,
-            &format_seconds(job.duration.unwrap_or_default()).to_string(),
-            &format_seconds(job.queued_duration.unwrap_or_default()).to_string(),
-        ];
-        if list_pipeline {
-            row.add_cell(Cell::new(
-                &(" ".repeat(start_position) + &"-".repeat(duration_width)),
-            ));
-        }
-        table.add_row(row);
+    let mut columns = select(
+        job_columns(),
+        &[
+            "ID",
+            "Pipeline",
+            "Ref",
+            "Status",
+            "Reason",
+            "Step",
+            "Artifacts",
+            "Name",
+            "Tags",
+            "Runner",
+            "Elapsed",
+            "Queued",
+        ],
+    );
+    // The histogram only makes sense to a human
+    if list_pipeline && output.is_table() {
+        columns.push(Column::new("Hist", |job: &Job| {
+            let duration = if job.status == "running" {
+                Utc::now() - job.started_at
+            } else {
+                job.finished_at - job.started_at
+            };
+            let start_position = (job.started_at - min).num_seconds() as f64 * scale;
+            let duration_width = duration.num_seconds() as f64 * scale;
+            let duration_width = duration_width.clamp(1.0, 30.0);
+            let start_position = start_position as usize;
+            let duration_width = duration_width as usize;
+            " ".repeat(start_position) + &"-".repeat(duration_width)
+        }));
     }
 
-    // Print the table to stdout
-    table.printstd();
+    output.print(&jobs, &columns)?;
 
-    println!("Jobs: {}", nr_jobs);
-    println!(
-        "Total artifacts produced: {}",
-        format_bytes(total_artifacts)
-    );
+    if output.is_table() {
+        println!("Jobs: {}", nr_jobs);
+        println!(
+            "Total artifacts produced: {}",
+            format_bytes(total_artifacts)
+        );
+    }
 
     Ok(())
 }
@@ -1,12 +1,14 @@
-use colored::*;
-use prettytable::{format, row, Table};
+use prettytable::Cell;
+use serde_derive::Serialize;
 
 use chrono::{DateTime, Utc};
 
-use crate::credentials::Credentials;
-use crate::format::{format_bytes, format_seconds};
+use crate::client::GitlabClient;
+use crate::error::GlcError;
+use crate::format::{format_bytes, format_seconds, format_status};
 use crate::job::{find_jobs, Job};
-use crate::pipeline::get_pipelines;
+use crate::output::{Column, Output};
+use crate::pipeline::{get_pipelines, Pipeline};
 
 // Returns number of seconds since the rfc3339 timestamp
 fn seconds_ago(datetime: String) -> isize {
@@ -18,47 +20,22 @@ fn seconds_ago(datetime: String) -> isize {
     (now - timestamp).num_seconds() as isize
 }
 
-pub async fn list_pipelines(
-    creds: &Credentials,
-    project: &str,
-    max_age: isize,
-    source: Option<String>,
-    rref: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let pipelines = get_pipelines(creds, project, max_age, source, rref).await?;
-
-    // Create a new table
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP);
-    table.set_titles(row![
-        "ID",
-        "Created",
-        "🔄 Status",
-        "PASS / FAIL /  RUN / PEND",
-        "Jobs",
-        "Artifacts",
-        "Elapsed",
-        "Source",
-        "SHA",
-        "Ref"
-    ]);
-
-    let pids = pipelines.iter().map(|p| p.id as usize).collect();
-    let all_jobs = find_jobs(creds, project, pids, None, None, None).await?;
-
-    let jobs: Vec<Vec<&Job>> = pipelines
-        .iter()
-        .map(|p| all_jobs.iter().filter(|j| j.pipeline.id == p.id).collect())
-        .collect();
+/// A pipeline along with the tally of its jobs
+#[derive(Serialize)]
+pub struct PipelineSummary {
+    #[serde(flatten)]
+    pub pipeline: Pipeline,
+    pub jobs: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub running: usize,
+    pub pending: usize,
+    pub artifacts_size: usize,
+    pub elapsed: Option<f64>,
+}
 
-    // Add a row per time
-    for (pipeline, jobs) in pipelines.iter().zip(jobs.into_iter()) {
-        let status = match pipeline.status.as_str() {
-            "success" => "✅ Success".green(),
-            "failed" => "❌ Failed".red(),
-            "running" => "⏳ Running".yellow(),
-            _ => "❓ Unknown".normal(),
-        };
+impl PipelineSummary {
+    fn new(pipeline: Pipeline, jobs: &[&Job]) -> PipelineSummary {
         let success = jobs.iter().filter(|j| j.status == "success").count();
         let failed = jobs.iter().filter(|j| j.status == "failed").count();
         let running = jobs.iter().filter(|j| j.status == "running").count();
@@ -67,39 +44,88 @@ pub async fn list_pipelines(
             pipeline.created_at.clone(),
             pipeline.updated_at.clone(),
         ) {
-            ("running", Some(c), _) => format_seconds(seconds_ago(c) as f64) + "+",
-            (_, Some(c), Some(u)) => format_seconds((seconds_ago(c) - seconds_ago(u)) as f64),
-            (_, _, _) => "-".to_string(),
+            ("running", Some(c), _) => Some(seconds_ago(c) as f64),
+            (_, Some(c), Some(u)) => Some((seconds_ago(c) - seconds_ago(u)) as f64),
+            (_, _, _) => None,
         };
-        let af_size: usize = jobs.iter().map(|j| j.artifacts_size).sum();
-        let status_str = format!(
-            "{:>4} / {:>4} / {:>4} / {:>4}",
+
+        PipelineSummary {
+            jobs: jobs.len(),
             success,
             failed,
             running,
-            jobs.len() - success - failed - running
-        );
-
-        let created = pipeline
-            .created_at
-            .clone()
-            .map_or("-".to_string(), |created| created);
-        table.add_row(row![
-            &pipeline.id,
-            &created,
-            &status,
-            &status_str,
-            &jobs.len(),
-            &format_bytes(af_size),
-            &elapsed,
-            &pipeline.source,
-            &pipeline.sha[..14].to_string(),
-            &pipeline.rref,
-        ]);
+            pending: jobs.len() - success - failed - running,
+            artifacts_size: jobs.iter().map(|j| j.artifacts_size).sum(),
+            elapsed,
+            pipeline,
+        }
     }
+}
+
+pub async fn list_pipelines(
+    client: &GitlabClient,
+    project: &str,
+    max_age: isize,
+    source: Option<String>,
+    rref: Option<String>,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let pipelines = get_pipelines(client, project, max_age, source, rref).await?;
+
+    let pids = pipelines.iter().map(|p| p.id as usize).collect();
+    let all_jobs = find_jobs(client, project, pids, None, None, None).await?;
+
+    let summaries: Vec<PipelineSummary> = pipelines
+        .into_iter()
+        .map(|p| {
+            let jobs: Vec<&Job> = all_jobs.iter().filter(|j| j.pipeline.id == p.id).collect();
+            PipelineSummary::new(p, &jobs)
+        })
+        .collect();
 
-    // Print the table to stdout
-    table.printstd();
+    let columns = vec![
+        Column::new("ID", |s: &PipelineSummary| s.pipeline.id.to_string()),
+        Column::new("Created", |s: &PipelineSummary| {
+            s.pipeline.created_at.clone().unwrap_or_default()
+        })
+        .human(|s: &PipelineSummary| Cell::new(s.pipeline.created_at.as_deref().unwrap_or("-"))),
+        Column::new("Status", |s: &PipelineSummary| s.pipeline.status.clone())
+            .human(|s: &PipelineSummary| Cell::new(&format_status(&s.pipeline.status).to_string())),
+        Column::new("PASS / FAIL /  RUN / PEND", |s: &PipelineSummary| {
+            format!("{}/{}/{}/{}", s.success, s.failed, s.running, s.pending)
+        })
+        .key("results")
+        .human(|s: &PipelineSummary| {
+            Cell::new(&format!(
+                "{:>4} / {:>4} / {:>4} / {:>4}",
+                s.success, s.failed, s.running, s.pending
+            ))
+        }),
+        Column::new("Jobs", |s: &PipelineSummary| s.jobs.to_string()),
+        Column::new("Artifacts", |s: &PipelineSummary| {
+            s.artifacts_size.to_string()
+        })
+        .human(|s: &PipelineSummary| Cell::new(&format_bytes(s.artifacts_size))),
+        Column::new("Elapsed", |s: &PipelineSummary| {
+            s.elapsed.map_or(String::new(), |e| e.to_string())
+        })
+        .key("duration")
+        .human(|s: &PipelineSummary| {
+            let elapsed = match s.elapsed {
+                Some(e) => format_seconds(e),
+                None => "-".to_string(),
+            };
+            if s.pipeline.status == "running" {
+                Cell::new(&(elapsed + "+"))
+            } else {
+                Cell::new(&elapsed)
+            }
+        }),
+        Column::new("Source", |s: &PipelineSummary| s.pipeline.source.clone()),
+        Column::new("SHA", |s: &PipelineSummary| s.pipeline.sha.clone())
+            .human(|s: &PipelineSummary| Cell::new(&s.pipeline.sha[..14])),
+        Column::new("Ref", |s: &PipelineSummary| s.pipeline.rref.clone()),
+    ];
 
-    Ok(())
+    output.print(&summaries, &columns)
 }
@@ -1,42 +1,26 @@
-use colored::*;
-use prettytable::{format, row, Table};
-use reqwest::Url;
-
-use crate::credentials::Credentials;
-use crate::pipeline::Pipeline;
-
-pub async fn list_projects(creds: &Credentials) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("{}/api/v4/projects", creds.url);
-    let url = Url::parse(&url)?;
-
-    let client = reqwest::Client::new();
-    let response = client.get(url).bearer_auth(&creds.token).send().await?;
-
-    let pipelines: Vec<Pipeline> = response.json().await?;
-
-    // Create a new table
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-    table.add_row(row!["ID", "Status", "Source", "ref"]);
-
-    // Add a row per time
-    for pipeline in pipelines {
-        let status = match pipeline.status.as_str() {
-            "success" => "✅ Success".green(),
-            "failed" => "❌ Failed".red(),
-            "running" => "⏳ Running".yellow(),
-            _ => "❓ Unknown".normal(),
-        };
-        table.add_row(row![
-            &pipeline.id.to_string(),
-            &status,
-            &pipeline.source,
-            &pipeline.rref,
-        ]);
-    }
-
-    // Print the table to stdout
-    table.printstd();
-
-    Ok(())
+use futures::TryStreamExt;
+
+use crate::client::GitlabClient;
+use crate::error::GlcError;
+use crate::output::{Column, Output};
+use crate::pagination::{paginate, Pagination};
+use crate::project::Project;
+
+pub async fn list_projects(client: &GitlabClient, output: &Output) -> Result<(), GlcError> {
+    // Without membership=true gitlab.com would list every public project
+    let url = client.api_url("projects?membership=true&simple=true&per_page=100")?;
+    let projects: Vec<Project> = paginate(client, url, Pagination::Keyset { order_by: "id" })
+        .try_collect()
+        .await?;
+
+    let columns = vec![
+        Column::new("ID", |p: &Project| p.id.to_string()),
+        Column::new("Path", |p: &Project| p.path_with_namespace.clone()),
+        Column::new("Default branch", |p: &Project| {
+            p.default_branch.clone().unwrap_or_default()
+        }),
+        Column::new("Last activity", |p: &Project| p.last_activity_at.clone()),
+    ];
+
+    output.print(&projects, &columns)
 }
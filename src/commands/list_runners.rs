@@ -1,11 +1,13 @@
-use crate::credentials::Credentials;
+use crate::client::GitlabClient;
+use crate::error::GlcError;
 use crate::job::get_runner_jobs;
-use crate::runner::{get_runner_detail, get_runners, Runner};
+use crate::output::{Column, Output};
+use crate::runner::{get_runner_detail, get_runners, Runner, RunnerDetail};
 
 use colored::*;
-use prettytable::{cell, format, row, Table};
+use prettytable::Cell;
+use serde_derive::Serialize;
 
-use anyhow::Result;
 use futures::future::try_join_all;
 
 fn opt(s: Option<String>) -> String {
@@ -15,61 +17,77 @@ fn opt(s: Option<String>) -> String {
     }
 }
 
-pub async fn list_runners(creds: &Credentials, max_age: isize) -> Result<()> {
-    let runners: Vec<Runner> = get_runners(creds).await?;
+/// A runner along with how its recent jobs went
+#[derive(Serialize)]
+pub struct RunnerSummary {
+    #[serde(flatten)]
+    pub detail: RunnerDetail,
+    pub success: usize,
+    pub failed: usize,
+    pub running: usize,
+}
+
+pub async fn list_runners(
+    client: &GitlabClient,
+    max_age: isize,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let runners: Vec<Runner> = get_runners(client).await?;
 
     let runner_details: Vec<_> = runners
         .iter()
-        .map(|r| get_runner_detail(&creds, &r))
+        .map(|r| get_runner_detail(client, r))
         .collect();
 
-    let mut table = Table::new();
-
-    // Set the headers
-    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-    table.add_row(row![
-        "ID",
-        "Version",
-        "Description",
-        "PASS / FAIL /  RUN",
-        "IP",
-        "Tags",
-        "Online",
-        "Active",
-        "Shared",
-        "Type"
-    ]);
-
     let runner_details = try_join_all(runner_details).await?;
     let jobs: Vec<_> = runner_details
         .iter()
-        .map(|d| get_runner_jobs(creds, d.id, max_age))
+        .map(|d| get_runner_jobs(client, d.id, max_age))
         .collect();
     let jobs = try_join_all(jobs).await?;
-    for (d, jobs) in runner_details.into_iter().zip(jobs.iter()) {
-        let success = jobs.into_iter().filter(|j| j.status == "success").count();
-        let failed = jobs.into_iter().filter(|j| j.status == "failed").count();
-        let running = jobs.into_iter().filter(|j| j.status == "running").count();
-        let status_str = format!("{:>4} / {:>4} / {:>4}", success, failed, running);
-        let online = match d.online {
-            Some(true) => "true".green(),
-            _ => "false".bright_red(),
-        };
-        table.add_row(row![
-            cell![&d.id.to_string()],
-            cell![&opt(d.version)],
-            cell![&d.description],
-            cell![status_str],
-            cell![&opt(d.ip_address)],
-            cell![&d.tag_list.join(", ")],
-            cell![&online],
-            cell![&d.active.to_string()],
-            cell![&d.is_shared.to_string()],
-            cell![&d.runner_type],
-        ]);
-    }
+    let summaries: Vec<RunnerSummary> = runner_details
+        .into_iter()
+        .zip(jobs.iter())
+        .map(|(detail, jobs)| RunnerSummary {
+            detail,
+            success: jobs.iter().filter(|j| j.status == "success").count(),
+            failed: jobs.iter().filter(|j| j.status == "failed").count(),
+            running: jobs.iter().filter(|j| j.status == "running").count(),
+        })
+        .collect();
 
-    table.printstd();
+    let columns = vec![
+        Column::new("ID", |s: &RunnerSummary| s.detail.id.to_string()),
+        Column::new("Version", |s: &RunnerSummary| opt(s.detail.version.clone())),
+        Column::new("Description", |s: &RunnerSummary| {
+            s.detail.description.clone()
+        }),
+        Column::new("PASS / FAIL /  RUN", |s: &RunnerSummary| {
+            format!("{}/{}/{}", s.success, s.failed, s.running)
+        })
+        .key("results")
+        .human(|s: &RunnerSummary| {
+            Cell::new(&format!(
+                "{:>4} / {:>4} / {:>4}",
+                s.success, s.failed, s.running
+            ))
+        }),
+        Column::new("IP", |s: &RunnerSummary| opt(s.detail.ip_address.clone())),
+        Column::new("Tags", |s: &RunnerSummary| s.detail.tag_list.join(", ")),
+        Column::new("Online", |s: &RunnerSummary| {
+            (s.detail.online == Some(true)).to_string()
+        })
+        .human(|s: &RunnerSummary| {
+            let online = match s.detail.online {
+                Some(true) => "true".green(),
+                _ => "false".bright_red(),
+            };
+            Cell::new(&online.to_string())
+        }),
+        Column::new("Active", |s: &RunnerSummary| s.detail.active.to_string()),
+        Column::new("Shared", |s: &RunnerSummary| s.detail.is_shared.to_string()),
+        Column::new("Type", |s: &RunnerSummary| s.detail.runner_type.clone()),
+    ];
 
-    Ok(())
+    output.print(&summaries, &columns)
 }
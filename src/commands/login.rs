@@ -1,22 +1,20 @@
-use serde_derive::{Deserialize, Serialize};
-use serde_yaml;
-use std::env;
-use std::fs::File;
 use std::io::{self, Write};
 
-#[derive(Serialize, Deserialize)]
-pub struct Credentials {
-    token: String,
-    url: String,
-}
+use reqwest::Url;
+
+use crate::credentials::{Credentials, Profiles};
+use crate::error::GlcError;
 
-pub fn login(url: &str) -> std::io::Result<()> {
+pub fn login(url: &str, profile: Option<&str>, project: Option<&str>) -> Result<(), GlcError> {
     let mut url = url.to_string();
 
     if !url.ends_with('/') {
         url.push('/');
     }
 
+    let mut profiles = Profiles::load()?;
+    let name = profile_name(&profiles, profile, &url)?;
+
     println!("Please visit {}-/profile/personal_access_tokens and create a token with the following permissions:", url);
     println!("read_api, read_user, read_repository, read_registry. Then copy paste the token to the prompt below.");
     print!("Token: ");
@@ -26,25 +24,74 @@ pub fn login(url: &str) -> std::io::Result<()> {
     io::stdin().read_line(&mut input)?;
     let input = input.trim();
 
-    let creds = Credentials {
-        token: input.to_string(),
-        url: url,
-    };
-    let creds_string = serde_yaml::to_string(&creds).map_err(|_| {
-        std::io::Error::new(std::io::ErrorKind::Other, "Failed to serialize credentials")
-    })?;
-    let home_dir = env::var("HOME").map_err(|_| {
-        std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Could not find home directory",
-        )
-    })?;
-    let creds_path = format!("{}/.creds", home_dir);
-    let mut file = File::create(&creds_path).map_err(|_| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to create credentials file at {}", creds_path),
-        )
-    })?;
-    file.write_all(creds_string.as_bytes())
+    // Logging in again to an existing profile keeps its default project,
+    // unless it was for another instance where the ID means something else
+    let project = project.map(str::to_string).or_else(|| {
+        profiles
+            .profiles
+            .get(&name)
+            .filter(|p| same_url(&p.url, &url))
+            .and_then(|p| p.project.clone())
+    });
+    profiles.profiles.insert(
+        name.clone(),
+        Credentials {
+            token: input.to_string(),
+            url,
+            project,
+        },
+    );
+    if profiles.default.is_none() {
+        profiles.default = Some(name.clone());
+    }
+    profiles.save()?;
+
+    println!("Saved credentials to profile {}", name);
+    Ok(())
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+// The profile to save the login to. Without --profile, that's the one in
+// use if it's for the same instance, or else the one profile already for
+// it, or else one named after the host, so that logging in elsewhere
+// doesn't replace the current credentials.
+fn profile_name(profiles: &Profiles, profile: Option<&str>, url: &str) -> Result<String, GlcError> {
+    if let Some(profile) = profile {
+        return Ok(profile.to_string());
+    }
+    // With several profiles and none the default, "default" may still be
+    // taken by another instance
+    let selected = profiles
+        .selected(None)
+        .unwrap_or_else(|| "default".to_string());
+    match profiles.profiles.get(&selected) {
+        Some(p) if !same_url(&p.url, url) => {}
+        _ => return Ok(selected),
+    }
+
+    // Some other profile may already be for this instance
+    let existing: Vec<&String> = profiles
+        .profiles
+        .iter()
+        .filter(|(_, p)| same_url(&p.url, url))
+        .map(|(name, _)| name)
+        .collect();
+    if let [name] = existing[..] {
+        return Ok(name.clone());
+    }
+
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .ok_or_else(|| GlcError::Config(format!("invalid GitLab URL {}", url)))?;
+    match profiles.profiles.get(&host) {
+        Some(p) if !same_url(&p.url, url) => Err(GlcError::Config(format!(
+            "profile {} is for {}, use --profile to pick a name for {}",
+            host, p.url, url
+        ))),
+        _ => Ok(host),
+    }
 }
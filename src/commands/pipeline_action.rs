@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use prettytable::Cell;
+
+use crate::client::{parse_json, GitlabClient};
+use crate::error::GlcError;
+use crate::format::format_status;
+use crate::job::is_active;
+use crate::output::{Column, Output};
+use crate::pipeline::{get_pipeline, get_pipelines, Pipeline};
+use crate::prompt::confirm;
+use crate::{parse_max_age, PipelineSelection};
+
+// Pipelines acted on at the same time
+const MAX_CONCURRENT: usize = 10;
+
+#[derive(Clone, Copy)]
+pub enum PipelineAction {
+    Retry,
+    Cancel,
+}
+
+impl PipelineAction {
+    fn verb(self) -> &'static str {
+        match self {
+            PipelineAction::Retry => "retry",
+            PipelineAction::Cancel => "cancel",
+        }
+    }
+
+    fn done(self) -> &'static str {
+        match self {
+            PipelineAction::Retry => "Retried",
+            PipelineAction::Cancel => "Canceled",
+        }
+    }
+
+    // Statuses picked when no --status is given
+    fn applies_to(self, status: &str) -> bool {
+        match self {
+            PipelineAction::Retry => matches!(status, "failed" | "canceled"),
+            PipelineAction::Cancel => is_active(status),
+        }
+    }
+}
+
+pub fn pipeline_columns() -> Vec<Column<'static, Pipeline>> {
+    vec![
+        Column::new("ID", |p: &Pipeline| p.id.to_string()),
+        Column::new("Created", |p: &Pipeline| {
+            p.created_at.clone().unwrap_or_default()
+        }),
+        Column::new("Status", |p: &Pipeline| p.status.clone())
+            .human(|p: &Pipeline| Cell::new(&format_status(&p.status).to_string())),
+        Column::new("Source", |p: &Pipeline| p.source.clone()),
+        Column::new("Ref", |p: &Pipeline| p.rref.clone()),
+        Column::new("URL", |p: &Pipeline| p.web_url.clone()),
+    ]
+}
+
+pub async fn pipeline_action(
+    client: &GitlabClient,
+    project: &str,
+    action: PipelineAction,
+    args: &PipelineSelection,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let pipelines = select_pipelines(client, project, args).await?;
+    let pipelines: Vec<Pipeline> = pipelines
+        .into_iter()
+        .filter(|p| match &args.status {
+            Some(s) => p.status.eq_ignore_ascii_case(s),
+            None => action.applies_to(&p.status),
+        })
+        .collect();
+
+    if pipelines.is_empty() {
+        eprintln!("No pipelines to {}", action.verb());
+        return Ok(());
+    }
+
+    output.print(&pipelines, &pipeline_columns())?;
+    if args.dry_run {
+        return Ok(());
+    }
+    let question = format!(
+        "About to {} {} pipelines, go ahead?",
+        action.verb(),
+        pipelines.len()
+    );
+    if !args.yes && !confirm(&question)? {
+        eprintln!("Nothing done");
+        return Ok(());
+    }
+
+    let done = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    stream::iter(&pipelines)
+        .for_each_concurrent(MAX_CONCURRENT, |p| {
+            let (done, failed) = (&done, &failed);
+            async move {
+                let path = format!("projects/{}/pipelines/{}/{}", project, p.id, action.verb());
+                let result = match client.post(&path).await {
+                    Ok(response) => parse_json::<Pipeline>(response).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(p) => {
+                        done.fetch_add(1, Ordering::Relaxed);
+                        println!("Pipeline {} is {}", p.id, p.status);
+                    }
+                    Err(e) => {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                        eprintln!("Failed to {} pipeline {}: {}", action.verb(), p.id, e);
+                    }
+                }
+            }
+        })
+        .await;
+
+    println!("{} {} pipelines", action.done(), done.into_inner());
+    match failed.into_inner() {
+        0 => Ok(()),
+        n => Err(GlcError::Other(anyhow::anyhow!(
+            "failed to {} {} pipelines",
+            action.verb(),
+            n
+        ))),
+    }
+}
+
+async fn select_pipelines(
+    client: &GitlabClient,
+    project: &str,
+    args: &PipelineSelection,
+) -> Result<Vec<Pipeline>, GlcError> {
+    if !args.pipelines.is_empty() {
+        let futures = args
+            .pipelines
+            .iter()
+            .map(|&id| get_pipeline(client, project, id));
+        return Ok(join_all(futures)
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()?);
+    }
+
+    let max_age = parse_max_age(&args.max_age)?;
+    // Oldest first
+    let pipelines = get_pipelines(client, project, max_age, None, args.rref.clone()).await?;
+    if !args.superseded {
+        return Ok(pipelines);
+    }
+
+    // Keep all but the newest pipeline of every ref, i.e. those made
+    // redundant by a later push
+    let mut newest = HashSet::new();
+    let mut superseded: Vec<Pipeline> = pipelines
+        .into_iter()
+        .rev()
+        .filter(|p| !newest.insert(p.rref.clone()))
+        .collect();
+    superseded.reverse();
+    Ok(superseded)
+}
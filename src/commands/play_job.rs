@@ -0,0 +1,100 @@
+use futures::future::join_all;
+use serde_derive::Serialize;
+
+use crate::client::{parse_json, GitlabClient};
+use crate::commands::show_job::show_job;
+use crate::error::GlcError;
+use crate::job::{find_jobs, get_job_details, Job};
+use crate::output::Output;
+use crate::ShowJobArgs;
+
+#[derive(Serialize)]
+struct JobVariable {
+    key: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct PlayRequest {
+    job_variables_attributes: Vec<JobVariable>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn play_job(
+    client: &GitlabClient,
+    project: &str,
+    jobs: Option<Vec<usize>>,
+    pipeline: Option<usize>,
+    job_names: Option<Vec<String>>,
+    variables: Vec<(String, String)>,
+    follow: bool,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let job_names: Option<Vec<&str>> = job_names
+        .as_ref()
+        .map(|vec| vec.iter().map(AsRef::as_ref).collect());
+    let jobs: Vec<Job> = if let Some(pipeline) = pipeline {
+        find_jobs(client, project, vec![pipeline], job_names, None, None)
+            .await?
+            .into_iter()
+            .filter(|j| j.status == "manual")
+            .collect()
+    } else {
+        let futures = jobs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|j| get_job_details(client, project, j));
+
+        let results = join_all(futures).await;
+        results.into_iter().collect::<anyhow::Result<Vec<Job>>>()?
+    };
+
+    if follow && jobs.len() != 1 {
+        return Err(GlcError::Config(format!(
+            "--follow needs exactly one job to play, found {}",
+            jobs.len()
+        )));
+    }
+
+    let request = PlayRequest {
+        job_variables_attributes: variables
+            .into_iter()
+            .map(|(key, value)| JobVariable { key, value })
+            .collect(),
+    };
+
+    eprintln!("Playing {} jobs...", jobs.len());
+
+    let mut played = Vec::new();
+    let mut failed = 0;
+    for job in jobs {
+        let path = format!("projects/{}/jobs/{}/play", project, job.id);
+        let result = match client.post_json(&path, &request).await {
+            Ok(response) => parse_json::<Job>(response).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(job) => {
+                println!("Job {} ({}) is {}", job.id, job.name, job.status);
+                played.push(job.id);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("Failed to play job {} ({}): {}", job.id, job.name, e);
+            }
+        }
+    }
+
+    // With --follow there's just the one job, so nothing to follow if it failed
+    if let (true, Some(&job)) = (follow, played.first()) {
+        show_job(client, project, &ShowJobArgs::follow(job), output).await?;
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(GlcError::Other(anyhow::anyhow!(
+            "failed to play {} jobs",
+            n
+        ))),
+    }
+}
@@ -0,0 +1,57 @@
+use prettytable::{format, row, Table};
+
+use crate::credentials::Profiles;
+use crate::error::GlcError;
+
+pub fn list_profiles(active: Option<&str>) -> Result<(), GlcError> {
+    let profiles = Profiles::load()?;
+    let active = profiles.selected(active);
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["", "Profile", "URL", "Project"]);
+
+    for (name, creds) in profiles.profiles.iter() {
+        let marker = if active.as_deref() == Some(name.as_str()) {
+            "*"
+        } else {
+            ""
+        };
+        table.add_row(row![
+            marker,
+            name,
+            &creds.url,
+            creds.project.as_deref().unwrap_or("-"),
+        ]);
+    }
+
+    table.printstd();
+
+    Ok(())
+}
+
+pub fn use_profile(name: &str) -> Result<(), GlcError> {
+    let mut profiles = Profiles::load()?;
+    if !profiles.profiles.contains_key(name) {
+        return Err(GlcError::Config(format!("no profile named {:?}", name)));
+    }
+    profiles.default = Some(name.to_string());
+    profiles.save()?;
+
+    println!("Now using profile {}", name);
+    Ok(())
+}
+
+pub fn remove_profile(name: &str) -> Result<(), GlcError> {
+    let mut profiles = Profiles::load()?;
+    if profiles.profiles.remove(name).is_none() {
+        return Err(GlcError::Config(format!("no profile named {:?}", name)));
+    }
+    if profiles.default.as_deref() == Some(name) {
+        profiles.default = None;
+    }
+    profiles.save()?;
+
+    println!("Removed profile {}", name);
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::stream::{self, StreamExt};
+use prettytable::Cell;
+use serde_derive::Serialize;
+
+use crate::client::GitlabClient;
+use crate::error::GlcError;
+use crate::format::{format_bytes, format_status};
+use crate::job::find_jobs;
+use crate::output::{Column, Output};
+use crate::pipeline::{get_old_pipelines, Pipeline};
+use crate::prompt::confirm;
+
+// Pipelines deleted at the same time
+const MAX_CONCURRENT: usize = 10;
+
+/// A pipeline up for deletion and the artifacts that go with it
+#[derive(Serialize)]
+pub struct PruneCandidate {
+    #[serde(flatten)]
+    pub pipeline: Pipeline,
+    pub artifacts_size: usize,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn prune_pipelines(
+    client: &GitlabClient,
+    project: &str,
+    older_than: isize,
+    source: Option<String>,
+    rref: Option<String>,
+    status: Option<String>,
+    yes: bool,
+    dry_run: bool,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let pipelines = get_old_pipelines(client, project, older_than, source, rref, status).await?;
+    if pipelines.is_empty() {
+        eprintln!("No pipelines to delete");
+        return Ok(());
+    }
+
+    let pids = pipelines.iter().map(|p| p.id as usize).collect();
+    let mut sizes: HashMap<u32, usize> = HashMap::new();
+    for job in find_jobs(client, project, pids, None, None, None).await? {
+        *sizes.entry(job.pipeline.id).or_default() += job.artifacts_size;
+    }
+    let candidates: Vec<PruneCandidate> = pipelines
+        .into_iter()
+        .map(|p| PruneCandidate {
+            artifacts_size: sizes.get(&p.id).copied().unwrap_or_default(),
+            pipeline: p,
+        })
+        .collect();
+    let total: usize = candidates.iter().map(|c| c.artifacts_size).sum();
+
+    // Always shown, so nothing is deleted without seeing what goes
+    output.print(&candidates, &candidate_columns())?;
+    eprintln!(
+        "{} pipelines, {} of artifacts",
+        candidates.len(),
+        format_bytes(total).trim()
+    );
+    if dry_run {
+        return Ok(());
+    }
+    let question = format!("Delete {} pipelines for good?", candidates.len());
+    if !yes && !confirm(&question)? {
+        eprintln!("Nothing deleted");
+        return Ok(());
+    }
+
+    let deleted = AtomicUsize::new(0);
+    let freed = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    stream::iter(&candidates)
+        .for_each_concurrent(MAX_CONCURRENT, |c| {
+            let (deleted, freed, failed) = (&deleted, &freed, &failed);
+            async move {
+                let path = format!("projects/{}/pipelines/{}", project, c.pipeline.id);
+                match client.delete(&path).await {
+                    Ok(_) => {
+                        deleted.fetch_add(1, Ordering::Relaxed);
+                        freed.fetch_add(c.artifacts_size, Ordering::Relaxed);
+                    }
+                    Err(e) => {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                        eprintln!("Failed to delete pipeline {}: {}", c.pipeline.id, e);
+                    }
+                }
+            }
+        })
+        .await;
+
+    println!(
+        "Deleted {} pipelines, freed {}",
+        deleted.into_inner(),
+        format_bytes(freed.into_inner()).trim()
+    );
+    match failed.into_inner() {
+        0 => Ok(()),
+        n => Err(GlcError::Other(anyhow::anyhow!(
+            "failed to delete {} pipelines",
+            n
+        ))),
+    }
+}
+
+fn candidate_columns() -> Vec<Column<'static, PruneCandidate>> {
+    vec![
+        Column::new("ID", |c: &PruneCandidate| c.pipeline.id.to_string()),
+        Column::new("Updated", |c: &PruneCandidate| {
+            c.pipeline.updated_at.clone().unwrap_or_default()
+        }),
+        Column::new("Status", |c: &PruneCandidate| c.pipeline.status.clone())
+            .human(|c: &PruneCandidate| Cell::new(&format_status(&c.pipeline.status).to_string())),
+        Column::new("Source", |c: &PruneCandidate| c.pipeline.source.clone()),
+        Column::new("Ref", |c: &PruneCandidate| c.pipeline.rref.clone()),
+        Column::new("Artifacts", |c: &PruneCandidate| {
+            c.artifacts_size.to_string()
+        })
+        .human(|c: &PruneCandidate| Cell::new(&format_bytes(c.artifacts_size))),
+    ]
+}
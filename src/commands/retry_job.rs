@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+
+use crate::client::{parse_json, GitlabClient};
+use crate::error::GlcError;
+use crate::job::{find_jobs, get_job_details, Job};
+
+pub async fn retry_job(
+    client: &GitlabClient,
+    project: &str,
+    jobs: Option<Vec<usize>>,
+    pipeline: Option<usize>,
+    job_names: Option<Vec<String>>,
+    status: Option<String>,
+    failure_reasons: Option<Vec<String>>,
+) -> Result<(), GlcError> {
+    let job_names: Option<Vec<&str>> = job_names
+        .as_ref()
+        .map(|vec| vec.iter().map(AsRef::as_ref).collect());
+    let jobs: Vec<Job> = if let Some(pipeline) = pipeline {
+        let jobs = find_jobs(client, project, vec![pipeline], job_names, None, None).await?;
+        latest_attempts(jobs)
+    } else {
+        let futures = jobs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|j| get_job_details(client, project, j));
+
+        let results = join_all(futures).await;
+        results.into_iter().collect::<anyhow::Result<Vec<Job>>>()?
+    };
+
+    // Filtered here rather than with the API's scope, which would turn up
+    // failed attempts that have since been retried
+    let jobs: Vec<Job> = jobs
+        .into_iter()
+        .filter(|j| match &status {
+            Some(s) => j.status.eq_ignore_ascii_case(s),
+            // Only what didn't go through, rather than re-running every
+            // job of the pipeline
+            None if pipeline.is_some() => is_retryable(&j.status),
+            None => true,
+        })
+        .filter(|j| {
+            failure_reasons.as_ref().is_none_or(|reasons| {
+                j.failure_reason
+                    .as_ref()
+                    .is_some_and(|r| reasons.contains(r))
+            })
+        })
+        .collect();
+
+    eprintln!("Retrying {} jobs...", jobs.len());
+
+    let mut failed = 0;
+    for job in jobs {
+        let path = format!("projects/{}/jobs/{}/retry", project, job.id);
+        let result = match client.post(&path).await {
+            Ok(response) => parse_json::<Job>(response).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(new_job) => println!("Job {} ({}) retried as {}", job.id, job.name, new_job.id),
+            Err(e) => {
+                failed += 1;
+                eprintln!("Failed to retry job {} ({}): {}", job.id, job.name, e);
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(GlcError::Other(anyhow::anyhow!(
+            "failed to retry {} jobs",
+            n
+        ))),
+    }
+}
+
+fn is_retryable(status: &str) -> bool {
+    matches!(status, "failed" | "canceled")
+}
+
+// A pipeline lists every attempt of a job, only the newest one can be
+// retried meaningfully
+fn latest_attempts(jobs: Vec<Job>) -> Vec<Job> {
+    let mut latest: HashMap<(u32, String), Job> = HashMap::new();
+    for job in jobs {
+        let key = (job.pipeline.id, job.name.clone());
+        if latest.get(&key).is_none_or(|j| j.id < job.id) {
+            latest.insert(key, job);
+        }
+    }
+    let mut jobs: Vec<Job> = latest.into_values().collect();
+    jobs.sort_by_key(|j| j.id);
+    jobs
+}
@@ -1,116 +1,198 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::Duration;
 
 use anyhow::Result;
-use prettytable::{format, row, table, Table};
-use reqwest::Url;
+use colored::{Color, Colorize};
+use futures::future::join_all;
+use prettytable::{format, row, table, Cell, Table};
+use serde_derive::Serialize;
+use tokio::time::sleep;
 
-use crate::credentials::Credentials;
+use crate::client::GitlabClient;
+use crate::error::GlcError;
 use crate::format::{format_bytes, format_seconds};
-use crate::job::{find_jobs, get_job_details, Job};
+use crate::job::{find_jobs, get_job_details, get_pipeline_jobs, is_active, Job};
+use crate::output::{Column, Output};
+use crate::pipeline::get_pipeline;
+use crate::trace::Trace;
 use crate::ShowJobArgs;
 
+// How often a followed job's log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Timing of one `section_start`/`section_end` pair of a job log
+#[derive(Serialize)]
+pub struct SectionStat {
+    pub job: usize,
+    pub section: String,
+    pub depth: usize,
+    /// None while the section is still running
+    pub duration: Option<f64>,
+    pub command: String,
+}
+
+fn section_columns(with_job: bool) -> Vec<Column<'static, SectionStat>> {
+    let mut columns = Vec::new();
+    if with_job {
+        columns.push(Column::new("Job", |s: &SectionStat| s.job.to_string()));
+    }
+    columns.extend([
+        Column::new("Section", |s: &SectionStat| s.section.clone())
+            .human(|s: &SectionStat| Cell::new(&("  ".repeat(s.depth) + &s.section))),
+        Column::new("Time", |s: &SectionStat| {
+            s.duration.map_or(String::new(), |d| d.to_string())
+        })
+        .human(|s: &SectionStat| match s.duration {
+            Some(d) => Cell::new(&format_seconds(d)).style_spec("r"),
+            None => Cell::new("(running)"),
+        }),
+        Column::new("Command", |s: &SectionStat| s.command.clone()),
+    ]);
+    columns
+}
+
+/// Tracks `section_start`/`section_end` markers as log lines come in,
+/// so a log can be parsed in pieces.
+struct SectionTracker {
+    job: usize,
+    // Sections are listed as they end, so nested ones come before their parent
+    stats: Vec<SectionStat>,
+    starts: HashMap<String, (usize, String)>,
+    open: Vec<String>,
+}
+
+impl SectionTracker {
+    fn new(job: usize) -> SectionTracker {
+        SectionTracker {
+            job,
+            stats: Vec::new(),
+            starts: HashMap::new(),
+            open: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, line: &str) {
+        let startidx: Vec<_> = line.match_indices("section_start:").collect();
+        let endidx: Vec<_> = line.match_indices("section_end:").collect();
+
+        for (idx, _) in itertools::merge(startidx, endidx) {
+            let sec = &line[idx..];
+            let mut f = sec.split(&[':', '\r', '\n'][..]);
+            match f.next() {
+                Some("section_start") => {
+                    let stime = f.next().and_then(|t| t.parse::<usize>().ok());
+                    let (Some(stime), Some(sstep)) = (stime, f.next()) else {
+                        continue;
+                    };
+                    let scmd = f.next().unwrap_or("none");
+                    let scmd = strip_ansi_escapes::strip_str(scmd);
+                    self.starts.insert(sstep.to_string(), (stime, scmd));
+                    self.open.push(sstep.to_string());
+                }
+                Some("section_end") => {
+                    let stime = f.next().and_then(|t| t.parse::<usize>().ok());
+                    let (Some(stime), Some(sstep)) = (stime, f.next()) else {
+                        continue;
+                    };
+                    let Some((start, cmd)) = self.starts.get(sstep) else {
+                        continue;
+                    };
+                    self.open.pop();
+                    self.stats.push(SectionStat {
+                        job: self.job,
+                        section: sstep.to_string(),
+                        depth: self.open.len(),
+                        duration: Some(stime.saturating_sub(*start) as f64),
+                        command: cmd.clone(),
+                    });
+                }
+                _ => {}
+            };
+        }
+    }
+
+    fn finish(mut self) -> Vec<SectionStat> {
+        // Whatever is still open hasn't finished yet
+        while let Some(sstep) = self.open.pop() {
+            self.stats.push(SectionStat {
+                job: self.job,
+                depth: self.open.len(),
+                duration: None,
+                command: self.starts[&sstep].1.clone(),
+                section: sstep,
+            });
+        }
+        self.stats
+    }
+}
+
+fn section_stats(job: &Job, log: &str) -> Vec<SectionStat> {
+    let mut sections = SectionTracker::new(job.id);
+    for line in log.lines() {
+        sections.feed(line);
+    }
+    sections.finish()
+}
+
+fn print_line(args: &ShowJobArgs, job: &Job, line: &str) {
+    let line = if args.plain {
+        strip_ansi_escapes::strip_str(line)
+    } else {
+        line.to_string()
+    };
+    if args.prefix {
+        println!("{}: {}", job.name, line);
+    } else {
+        println!("{}", line);
+    }
+}
+
 pub async fn show_job(
-    creds: &Credentials,
+    client: &GitlabClient,
     project: &str,
     args: &ShowJobArgs,
-) -> Result<(), Box<dyn std::error::Error>> {
+    output: &Output,
+) -> Result<(), GlcError> {
+    if args.follow {
+        return match args.pipeline {
+            Some(pipeline) => follow_pipeline(client, project, pipeline, args, output).await,
+            None => follow_job(client, project, args, output).await,
+        };
+    }
+
     let jobs: Vec<Job> = if let Some(pipeline) = args.pipeline {
-        find_jobs(creds, project, vec![pipeline], None, None, None).await?
+        find_jobs(client, project, vec![pipeline], None, None, None).await?
     } else {
-        vec![
-            get_job_details(
-                Arc::new(creds.clone()),
-                project.to_string(),
-                args.job.unwrap(),
-            )
-            .await?,
-        ]
+        vec![get_job_details(client, project, args.job.unwrap()).await?]
     };
 
-    let creds = Arc::new(creds.clone());
-    let project = project.to_string();
-
+    let mut all_stats = Vec::new();
     for job in jobs.iter() {
-        let log = get_job_logs(Arc::clone(&creds), project.clone(), job.id).await?;
-        let log = if args.plain {
-            strip_ansi_escapes::strip_str(log)
-        } else {
-            log
-        };
+        let log = get_job_logs(client, project, job.id).await?;
 
         if args.stats {
-            let mut starts = HashMap::new();
-
-            let mut level = 0;
-
-            let mut table = Table::new();
-            table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-            let titles = row!["Section", "Time", "Command"];
-            table.set_titles(titles);
-
-            let mut cursteps: Vec<_> = Vec::new();
-            let startidx: Vec<_> = log.match_indices("section_start:").collect();
-            let endidx: Vec<_> = log.match_indices("section_end:").collect();
-
-            for (idx, _) in itertools::merge(startidx, endidx) {
-                let sec = &log[idx..];
-                let mut f = sec.split(&[':', '\r', '\n'][..]);
-                match f.next() {
-                    Some("section_start") => {
-                        let stime = f.next().unwrap();
-                        let stime = stime.parse::<usize>().unwrap();
-                        let sstep = f.next().unwrap();
-                        let scmd = f.next().unwrap_or("none");
-                        let scmd = strip_ansi_escapes::strip_str(scmd);
-                        starts.insert(sstep.to_string(), (stime, scmd.to_string()));
-                        cursteps.push(sstep);
-                        level += 1;
-                    }
-                    Some("section_end") => {
-                        level -= 1;
-                        cursteps.pop();
-                        let stime = f.next().unwrap();
-                        let stime = stime.parse::<usize>().unwrap();
-                        let sstep = f.next().unwrap();
-                        table.add_row(row![
-                            "  ".repeat(level) + sstep,
-                            r->format_seconds((stime - starts[sstep].0) as f64),
-                            starts[sstep].1
-                        ]);
-                    }
-                    None => {
-                        while let Some(sstep) = cursteps.pop() {
-                            table.add_row(row![
-                                "  ".repeat(level) + sstep,
-                                "(running)",
-                                starts[sstep].1
-                            ]);
-                            level -= 1;
-                        }
-                    }
-                    Some(s) => {
-                        println!("Unknown section {}", s);
-                    }
-                };
+            let stats = section_stats(job, &log);
+            if output.is_table() {
+                output.print(&stats, &section_columns(false))?;
+            } else {
+                all_stats.extend(stats);
             }
-            table.printstd();
         } else {
             let skip = match args.tail {
                 Some(t) => log.lines().count().saturating_sub(t),
                 _ => 0,
             };
             for l in log.lines().skip(skip) {
-                let l = if args.prefix {
-                    format!("{}: {}", job.name, l)
-                } else {
-                    l.to_string()
-                };
-                println!("{}", l);
+                print_line(args, job, l);
             }
         }
     }
 
+    if args.stats && !output.is_table() {
+        output.print(&all_stats, &section_columns(true))?;
+    }
+
     /*
     let logs: Result<Vec<String>, anyhow::Error> = async {
         let futures: Vec<_> = jobs
@@ -140,55 +222,238 @@ pub async fn show_job(
 
     // Now job_details and job_logs are available, you can print them or process further
 
-    if args.status {
+    if args.status && output.is_table() {
         for job in &jobs {
-            let mut artifact_table = match &job.artifacts {
-                Some(a) => a
-                    .iter()
-                    .map(|a| row!(a.filename, format_bytes(a.size)))
-                    .collect::<Table>(),
-                None => table![],
-            };
-            artifact_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-            artifact_table.set_titles(row!("filename", "size"));
-
-            let mut table = table!(
-                ["ID", job.id],
-                ["Status", job.status],
-                ["Stage", job.stage],
-                ["Name", job.name],
-                ["Artifact size", format_bytes(job.artifacts_size)],
-                ["Artifacts", artifact_table],
-                ["Started at", job.started_at],
-                ["Finished at", job.finished_at],
-                [
-                    "Duration",
-                    &format_seconds(job.duration.unwrap_or_default())
-                ],
-                ["Runner", job.runner.as_ref().unwrap().description],
-                ["", ""],
-                ["Ref", job.pipeline.rref],
-                ["Source", job.pipeline.source],
-                ["Pipeline URL", job.pipeline.web_url]
-            );
-
-            table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-            table.printstd();
+            print_summary(job);
         }
     }
 
     Ok(())
 }
 
-async fn get_job_logs(creds: Arc<Credentials>, project: String, job: usize) -> Result<String> {
-    let url = format!(
-        "{}/api/v4/projects/{}/jobs/{}/trace",
-        creds.url, project, job
+// Prints the log as it grows until the job is done, then fails unless
+// the job succeeded
+async fn follow_job(
+    client: &GitlabClient,
+    project: &str,
+    args: &ShowJobArgs,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let job_id = args.job.unwrap();
+    let mut trace = Trace::new(project, job_id);
+    let mut sections = SectionTracker::new(job_id);
+    let mut tail = args.tail;
+
+    let job = loop {
+        // Status first, so the read after the job is done gets the whole log
+        let job = get_job_details(client, project, job_id).await?;
+        let done = !is_active(&job.status);
+        let mut lines = trace.read(client).await?;
+        if done {
+            lines.extend(trace.rest());
+        }
+
+        // --tail only applies to what was there before following
+        let skip = match tail.take() {
+            Some(t) => lines.len().saturating_sub(t),
+            None => 0,
+        };
+        for (i, line) in lines.iter().enumerate() {
+            sections.feed(line);
+            if !args.stats && i >= skip {
+                print_line(args, &job, line);
+            }
+        }
+        io::stdout().flush()?;
+
+        if done {
+            break job;
+        }
+        sleep(FOLLOW_INTERVAL).await;
+    };
+
+    if args.stats {
+        let with_job = !output.is_table();
+        output.print(&sections.finish(), &section_columns(with_job))?;
+    }
+    if args.status && output.is_table() {
+        print_summary(&job);
+    }
+
+    match job.status.as_str() {
+        "success" => Ok(()),
+        status => Err(GlcError::Unsuccessful(
+            format!("job {}", job.id),
+            status.to_string(),
+        )),
+    }
+}
+
+// A job of a followed pipeline that is being printed
+struct FollowedJob {
+    job: Job,
+    trace: Trace,
+    sections: SectionTracker,
+    tail: Option<usize>,
+    colour: Color,
+}
+
+// Prints the logs of every job in the pipeline as they run, picking up
+// jobs as they start, until the pipeline is done
+async fn follow_pipeline(
+    client: &GitlabClient,
+    project: &str,
+    pipeline_id: usize,
+    args: &ShowJobArgs,
+    output: &Output,
+) -> Result<(), GlcError> {
+    const COLOURS: [Color; 6] = [
+        Color::Cyan,
+        Color::Yellow,
+        Color::Green,
+        Color::Magenta,
+        Color::Blue,
+        Color::Red,
+    ];
+
+    let mut followed: BTreeMap<usize, FollowedJob> = BTreeMap::new();
+    // Jobs seen waiting to run, so they are followed even if they are
+    // already done by the time they are seen again
+    let mut waiting = HashSet::new();
+    let mut finished = HashSet::new();
+    let mut started = 0;
+    let mut width = 0;
+    let mut stats = Vec::new();
+
+    let pipeline = loop {
+        // Pipeline first, so the jobs read after it is done are all done too
+        let pipeline = get_pipeline(client, project, pipeline_id).await?;
+        for job in get_pipeline_jobs(client, project, pipeline_id).await? {
+            if finished.contains(&job.id) {
+                continue;
+            } else if let Some(f) = followed.get_mut(&job.id) {
+                f.job = job;
+            } else if job.status == "running"
+                || (waiting.contains(&job.id) && !is_active(&job.status))
+            {
+                width = width.max(job.name.len());
+                followed.insert(
+                    job.id,
+                    FollowedJob {
+                        trace: Trace::new(project, job.id),
+                        sections: SectionTracker::new(job.id),
+                        tail: args.tail,
+                        colour: COLOURS[started % COLOURS.len()],
+                        job,
+                    },
+                );
+                started += 1;
+            } else if is_active(&job.status) {
+                waiting.insert(job.id);
+            }
+        }
+
+        let reads = join_all(followed.values_mut().map(|f| f.trace.read(client))).await;
+        let mut done = Vec::new();
+        for (f, lines) in followed.values_mut().zip(reads) {
+            let mut lines = lines?;
+            if !is_active(&f.job.status) {
+                lines.extend(f.trace.rest());
+                done.push(f.job.id);
+            }
+
+            let skip = match f.tail.take() {
+                Some(t) => lines.len().saturating_sub(t),
+                None => 0,
+            };
+            let prefix = format!("{:width$} |", f.job.name);
+            let prefix = if args.plain {
+                prefix.normal()
+            } else {
+                prefix.color(f.colour)
+            };
+            for (i, line) in lines.iter().enumerate() {
+                f.sections.feed(line);
+                if args.stats || i < skip {
+                    continue;
+                }
+                if args.plain {
+                    println!("{} {}", prefix, strip_ansi_escapes::strip_str(line));
+                } else {
+                    println!("{} {}", prefix, line);
+                }
+            }
+        }
+        io::stdout().flush()?;
+
+        for id in done {
+            if let Some(f) = followed.remove(&id) {
+                stats.extend(f.sections.finish());
+                finished.insert(id);
+            }
+        }
+        if !is_active(&pipeline.status) && followed.is_empty() {
+            break pipeline;
+        }
+        sleep(FOLLOW_INTERVAL).await;
+    };
+
+    if args.stats {
+        output.print(&stats, &section_columns(true))?;
+    }
+
+    match pipeline.status.as_str() {
+        "success" => Ok(()),
+        status => Err(GlcError::Unsuccessful(
+            format!("pipeline {}", pipeline.id),
+            status.to_string(),
+        )),
+    }
+}
+
+fn print_summary(job: &Job) {
+    let mut artifact_table = match &job.artifacts {
+        Some(a) => a
+            .iter()
+            .map(|a| row!(a.filename, format_bytes(a.size)))
+            .collect::<Table>(),
+        None => table![],
+    };
+    artifact_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    artifact_table.set_titles(row!("filename", "size"));
+
+    let mut table = table!(
+        ["ID", job.id],
+        ["Status", job.status],
+        ["Stage", job.stage],
+        ["Name", job.name],
+        ["Artifact size", format_bytes(job.artifacts_size)],
+        ["Artifacts", artifact_table],
+        ["Started at", job.started_at],
+        ["Finished at", job.finished_at],
+        [
+            "Duration",
+            &format_seconds(job.duration.unwrap_or_default())
+        ],
+        // Jobs canceled before they were picked up have no runner
+        [
+            "Runner",
+            job.runner.as_ref().map_or("-", |r| r.description.as_str())
+        ],
+        ["", ""],
+        ["Ref", job.pipeline.rref],
+        ["Source", job.pipeline.source],
+        ["Pipeline URL", job.pipeline.web_url]
     );
-    let url = Url::parse(&url)?;
 
-    let client = reqwest::Client::new();
-    let response = client.get(url).bearer_auth(&creds.token).send().await?;
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.printstd();
+}
+
+async fn get_job_logs(client: &GitlabClient, project: &str, job: usize) -> Result<String> {
+    let response = client
+        .get(&format!("projects/{}/jobs/{}/trace", project, job))
+        .await?;
 
     let logs = response.text().await?;
 
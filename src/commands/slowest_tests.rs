@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use clap::ValueEnum;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use prettytable::Cell;
+use serde_derive::Serialize;
+
+use crate::client::GitlabClient;
+use crate::commands::test_report::{
+    format_test_time, get_test_report, get_test_report_summary, TestSuite, TestSuiteSummary,
+};
+use crate::error::GlcError;
+use crate::output::{Column, Output};
+use crate::pipeline::{created_at, get_pipelines, Pipeline};
+
+// Test reports fetched at the same time
+const MAX_CONCURRENT: usize = 10;
+
+// Mean times closer than this to the previous window's count as unchanged
+const TREND_THRESHOLD: f64 = 0.1;
+
+/// What to rank the slowest by
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RankBy {
+    /// Mean execution time
+    #[default]
+    Mean,
+    /// 95th percentile execution time
+    P95,
+}
+
+/// Execution times of a test (or suite) over the window
+#[derive(Serialize)]
+pub struct Timing {
+    pub suite: String,
+    pub test: Option<String>,
+    pub runs: usize,
+    pub mean: f64,
+    pub p95: f64,
+    /// Mean in the window before, if it ran then
+    pub previous_mean: Option<f64>,
+}
+
+impl Timing {
+    fn new(suite: String, test: Option<String>, mut times: Vec<f64>) -> Timing {
+        times.sort_by(f64::total_cmp);
+        // Nearest rank
+        let rank = (times.len() as f64 * 0.95).ceil() as usize;
+        Timing {
+            suite,
+            test,
+            runs: times.len(),
+            mean: mean(&times),
+            p95: times[rank.max(1) - 1],
+            previous_mean: None,
+        }
+    }
+
+    fn time(&self, by: RankBy) -> f64 {
+        match by {
+            RankBy::Mean => self.mean,
+            RankBy::P95 => self.p95,
+        }
+    }
+
+    fn trend(&self) -> Option<f64> {
+        self.previous_mean
+            .filter(|p| *p > 0.0)
+            .map(|p| (self.mean - p) / p)
+    }
+}
+
+fn mean(times: &[f64]) -> f64 {
+    times.iter().sum::<f64>() / times.len() as f64
+}
+
+fn trend_cell(timing: &Timing) -> Cell {
+    match timing.trend() {
+        None => Cell::new("new").style_spec("c"),
+        Some(t) if t > TREND_THRESHOLD => {
+            Cell::new(&format!("↑ {:+.0}%", t * 100.0)).style_spec("rFr")
+        }
+        Some(t) if t < -TREND_THRESHOLD => {
+            Cell::new(&format!("↓ {:+.0}%", t * 100.0)).style_spec("rFg")
+        }
+        Some(_) => Cell::new("→").style_spec("r"),
+    }
+}
+
+fn timing_columns(suites: bool) -> Vec<Column<'static, Timing>> {
+    let mut columns = vec![Column::new("Suite", |t: &Timing| t.suite.clone())];
+    if !suites {
+        columns.push(Column::new("Test", |t: &Timing| {
+            t.test.clone().unwrap_or_default()
+        }));
+    }
+    columns.extend([
+        Column::new("Runs", |t: &Timing| t.runs.to_string())
+            .human(|t: &Timing| Cell::new(&t.runs.to_string()).style_spec("r")),
+        Column::new("Mean", |t: &Timing| t.mean.to_string())
+            .human(|t: &Timing| Cell::new(&format_test_time(t.mean)).style_spec("r")),
+        Column::new("P95", |t: &Timing| t.p95.to_string())
+            .human(|t: &Timing| Cell::new(&format_test_time(t.p95)).style_spec("r")),
+        Column::new("Previous", |t: &Timing| {
+            t.previous_mean.map(|p| p.to_string()).unwrap_or_default()
+        })
+        .human(|t: &Timing| {
+            Cell::new(&t.previous_mean.map(format_test_time).unwrap_or_default()).style_spec("r")
+        }),
+        Column::new("Trend", |t: &Timing| {
+            t.trend().map(|t| t.to_string()).unwrap_or_default()
+        })
+        .human(trend_cell),
+    ]);
+    columns
+}
+
+// Suite and test name, without one when ranking suites
+type Key = (String, Option<String>);
+
+// Times per test (or suite)
+type Times = HashMap<Key, Vec<f64>>;
+
+async fn collect_times(
+    client: &GitlabClient,
+    project: &str,
+    pipelines: Vec<Pipeline>,
+    suites: bool,
+    suite: Option<&str>,
+) -> Result<Times, GlcError> {
+    let results: Vec<Vec<(Key, f64)>> = stream::iter(pipelines)
+        .map(|p| async move {
+            let pipeline = p.id as usize;
+            let times = if suites {
+                get_test_report_summary(client, project, pipeline)
+                    .await?
+                    .test_suites
+                    .into_iter()
+                    .filter(|s| suite.is_none_or(|suite| suite == s.name))
+                    .filter(|s| s.total_count > s.skipped_count)
+                    .map(|s: TestSuiteSummary| ((s.name, None), s.total_time))
+                    .collect()
+            } else {
+                get_test_report(client, project, pipeline)
+                    .await?
+                    .test_suites
+                    .into_iter()
+                    .filter(|s| suite.is_none_or(|suite| suite == s.name))
+                    .flat_map(|s: TestSuite| {
+                        let suite = s.name;
+                        s.test_cases
+                            .into_iter()
+                            // Skipped tests didn't take any time
+                            .filter(|c| c.status != "skipped")
+                            .map(move |c| {
+                                let test = match c.classname.is_empty() {
+                                    true => c.name,
+                                    false => format!("{}.{}", c.classname, c.name),
+                                };
+                                ((suite.clone(), Some(test)), c.execution_time)
+                            })
+                    })
+                    .collect()
+            };
+            Ok::<_, anyhow::Error>(times)
+        })
+        .buffered(MAX_CONCURRENT)
+        .try_collect()
+        .await?;
+
+    let mut times = Times::new();
+    for (key, time) in results.into_iter().flatten() {
+        times.entry(key).or_default().push(time);
+    }
+    Ok(times)
+}
+
+/// Ranks tests, or suites, by their mean or p95 execution time in the pipelines
+/// of the last `max_age` seconds, next to how they did in the window
+/// before that.
+#[allow(clippy::too_many_arguments)]
+pub async fn slowest_tests(
+    client: &GitlabClient,
+    project: &str,
+    count: usize,
+    by: RankBy,
+    suites: bool,
+    suite: Option<String>,
+    max_age: isize,
+    source: Option<String>,
+    rref: Option<String>,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let pipelines = get_pipelines(client, project, max_age.saturating_mul(2), source, rref).await?;
+    let window_start = Utc::now() - Duration::seconds(max_age.min(i32::MAX as isize) as i64);
+    let (current, previous): (Vec<Pipeline>, Vec<Pipeline>) = pipelines
+        .into_iter()
+        .partition(|p| created_at(p) >= window_start);
+
+    let current = collect_times(client, project, current, suites, suite.as_deref()).await?;
+    let previous = collect_times(client, project, previous, suites, suite.as_deref()).await?;
+
+    let mut timings: Vec<Timing> = current
+        .into_iter()
+        .map(|((suite, test), times)| {
+            let previous_mean = previous
+                .get(&(suite.clone(), test.clone()))
+                .map(|times| mean(times));
+            Timing {
+                previous_mean,
+                ..Timing::new(suite, test, times)
+            }
+        })
+        .collect();
+    if timings.is_empty() {
+        eprintln!("No test results found");
+        return Ok(());
+    }
+    timings.sort_by(|a, b| b.time(by).total_cmp(&a.time(by)));
+    timings.truncate(count);
+
+    output.print(&timings, &timing_columns(suites))
+}
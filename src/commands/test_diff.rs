@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use prettytable::Cell;
+use serde_derive::Serialize;
+
+use crate::client::GitlabClient;
+use crate::commands::test_report::{format_test_time, get_test_report, TestCase, TestReport};
+use crate::error::GlcError;
+use crate::output::{Column, Output};
+
+// A test counts as significantly slower when it takes this many times
+// as long as before, and at least MIN_SLOWDOWN seconds more
+const SLOWDOWN_FACTOR: f64 = 1.5;
+const MIN_SLOWDOWN: f64 = 0.5;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    NewlyFailing,
+    StillFailing,
+    NewlyPassing,
+    Added,
+    Removed,
+    Slower,
+}
+
+impl Change {
+    fn describe(self) -> &'static str {
+        match self {
+            Change::NewlyFailing => "newly failing",
+            Change::StillFailing => "still failing",
+            Change::NewlyPassing => "newly passing",
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Slower => "slower",
+        }
+    }
+}
+
+/// How one test differs between the base and head pipelines
+#[derive(Serialize)]
+pub struct TestChange {
+    pub change: Change,
+    pub suite: String,
+    pub classname: String,
+    pub name: String,
+    pub base_status: Option<String>,
+    pub head_status: Option<String>,
+    pub base_time: Option<f64>,
+    pub head_time: Option<f64>,
+}
+
+type TestKey = (String, String, String);
+
+fn cases(report: TestReport) -> BTreeMap<TestKey, TestCase> {
+    report
+        .test_suites
+        .into_iter()
+        .flat_map(|s| {
+            let suite = s.name;
+            s.test_cases
+                .into_iter()
+                .map(move |c| ((suite.clone(), c.classname.clone(), c.name.clone()), c))
+        })
+        .collect()
+}
+
+fn classify(base: Option<&TestCase>, head: Option<&TestCase>) -> Option<Change> {
+    match (base, head) {
+        (None, Some(_)) => Some(Change::Added),
+        (Some(_), None) => Some(Change::Removed),
+        (Some(b), Some(h)) => match (b.is_failure(), h.is_failure()) {
+            (false, true) => Some(Change::NewlyFailing),
+            (true, true) => Some(Change::StillFailing),
+            (true, false) if h.status == "success" => Some(Change::NewlyPassing),
+            _ if h.execution_time > b.execution_time * SLOWDOWN_FACTOR
+                && h.execution_time - b.execution_time >= MIN_SLOWDOWN =>
+            {
+                Some(Change::Slower)
+            }
+            _ => None,
+        },
+        (None, None) => None,
+    }
+}
+
+fn time_cell(time: Option<f64>) -> Cell {
+    Cell::new(&time.map_or("-".to_string(), format_test_time)).style_spec("r")
+}
+
+fn change_columns() -> Vec<Column<'static, TestChange>> {
+    vec![
+        Column::new("Change", |c: &TestChange| c.change.describe().to_string()).human(
+            |c: &TestChange| {
+                let cell = Cell::new(c.change.describe());
+                match c.change {
+                    Change::NewlyFailing => cell.style_spec("bFr"),
+                    Change::StillFailing => cell.style_spec("Fr"),
+                    Change::NewlyPassing => cell.style_spec("Fg"),
+                    Change::Slower => cell.style_spec("Fy"),
+                    Change::Added | Change::Removed => cell,
+                }
+            },
+        ),
+        Column::new("Suite", |c: &TestChange| c.suite.clone()),
+        Column::new("Class", |c: &TestChange| c.classname.clone()),
+        Column::new("Name", |c: &TestChange| c.name.clone()),
+        Column::new("Base", |c: &TestChange| {
+            c.base_status.clone().unwrap_or_default()
+        }),
+        Column::new("Head", |c: &TestChange| {
+            c.head_status.clone().unwrap_or_default()
+        }),
+        Column::new("Base time", |c: &TestChange| {
+            c.base_time.map_or(String::new(), |t| t.to_string())
+        })
+        .human(|c: &TestChange| time_cell(c.base_time)),
+        Column::new("Head time", |c: &TestChange| {
+            c.head_time.map_or(String::new(), |t| t.to_string())
+        })
+        .key("duration")
+        .human(|c: &TestChange| time_cell(c.head_time)),
+    ]
+}
+
+fn totals(pipeline: usize, report: &TestReport) -> String {
+    format!(
+        "{}: {} tests, {} passed, {} failed, {} skipped, {} errors in {}",
+        pipeline,
+        report.total_count,
+        report.success_count,
+        report.failed_count,
+        report.skipped_count,
+        report.error_count,
+        format_test_time(report.total_time)
+    )
+}
+
+pub async fn test_diff(
+    client: &GitlabClient,
+    project: &str,
+    base: usize,
+    head: usize,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let (base_report, head_report) = futures::try_join!(
+        get_test_report(client, project, base),
+        get_test_report(client, project, head)
+    )?;
+
+    if output.is_table() {
+        println!("Base {}", totals(base, &base_report));
+        println!("Head {}", totals(head, &head_report));
+    }
+
+    let mut base_cases = cases(base_report);
+    let head_cases = cases(head_report);
+
+    let mut changes: Vec<TestChange> = Vec::new();
+    for (key, h) in &head_cases {
+        let b = base_cases.remove(key);
+        if let Some(change) = classify(b.as_ref(), Some(h)) {
+            changes.push(TestChange {
+                change,
+                suite: key.0.clone(),
+                classname: key.1.clone(),
+                name: key.2.clone(),
+                base_status: b.as_ref().map(|b| b.status.clone()),
+                head_status: Some(h.status.clone()),
+                base_time: b.as_ref().map(|b| b.execution_time),
+                head_time: Some(h.execution_time),
+            });
+        }
+    }
+    // What's left only ran in the base pipeline
+    for ((suite, classname, name), b) in base_cases {
+        changes.push(TestChange {
+            change: Change::Removed,
+            suite,
+            classname,
+            name,
+            base_status: Some(b.status),
+            head_status: None,
+            base_time: Some(b.execution_time),
+            head_time: None,
+        });
+    }
+    changes.sort_by_key(|c| c.change);
+
+    if changes.is_empty() && output.is_table() {
+        println!("No differences");
+        return Ok(());
+    }
+    output.print(&changes, &change_columns())
+}
@@ -0,0 +1,135 @@
+use futures::stream::{self, StreamExt, TryStreamExt};
+use prettytable::Cell;
+use serde_derive::Serialize;
+
+use crate::client::GitlabClient;
+use crate::commands::test_report::{format_test_time, get_test_report, TestCase};
+use crate::error::GlcError;
+use crate::format::format_status;
+use crate::output::{Column, Output};
+use crate::pipeline::{get_pipelines, Pipeline};
+
+// Test reports fetched at the same time
+const MAX_CONCURRENT: usize = 10;
+
+/// One run of the test, in one pipeline
+#[derive(Serialize)]
+pub struct TestRun {
+    pub pipeline: u32,
+    pub created_at: Option<String>,
+    #[serde(rename = "ref")]
+    pub rref: String,
+    pub sha: String,
+    pub suite: String,
+    #[serde(flatten)]
+    pub case: TestCase,
+}
+
+fn run_columns() -> Vec<Column<'static, TestRun>> {
+    vec![
+        Column::new("Pipeline", |r: &TestRun| r.pipeline.to_string()),
+        Column::new("Created", |r: &TestRun| {
+            r.created_at.clone().unwrap_or_default()
+        }),
+        Column::new("Ref", |r: &TestRun| r.rref.clone()),
+        Column::new("SHA", |r: &TestRun| r.sha.clone())
+            .human(|r: &TestRun| Cell::new(&r.sha[..r.sha.len().min(14)])),
+        Column::new("Suite", |r: &TestRun| r.suite.clone()),
+        Column::new("Status", |r: &TestRun| r.case.status.clone())
+            .human(|r: &TestRun| Cell::new(&format_status(&r.case.status).to_string())),
+        Column::new("Time", |r: &TestRun| r.case.execution_time.to_string())
+            .key("duration")
+            .human(|r: &TestRun| {
+                Cell::new(&format_test_time(r.case.execution_time)).style_spec("r")
+            }),
+    ]
+}
+
+// Either the bare test name or classname.name
+fn is_test(case: &TestCase, name: &str) -> bool {
+    case.name == name || format!("{}.{}", case.classname, case.name) == name
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn test_history(
+    client: &GitlabClient,
+    project: &str,
+    name: &str,
+    suite: Option<String>,
+    max_age: isize,
+    source: Option<String>,
+    rref: Option<String>,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let pipelines = get_pipelines(client, project, max_age, source, rref).await?;
+
+    // Oldest first, like the pipelines
+    let runs: Vec<Vec<TestRun>> = stream::iter(pipelines)
+        .map(|p: Pipeline| {
+            let suite = suite.clone();
+            async move {
+                let report = get_test_report(client, project, p.id as usize).await?;
+                let runs: Vec<TestRun> = report
+                    .test_suites
+                    .into_iter()
+                    .filter(|s| suite.as_deref().is_none_or(|suite| suite == s.name))
+                    .flat_map(|s| {
+                        let suite = s.name;
+                        s.test_cases
+                            .into_iter()
+                            .filter(|c| is_test(c, name))
+                            .map(move |case| (suite.clone(), case))
+                    })
+                    .map(|(suite, case)| TestRun {
+                        pipeline: p.id,
+                        created_at: p.created_at.clone(),
+                        rref: p.rref.clone(),
+                        sha: p.sha.clone(),
+                        suite,
+                        case,
+                    })
+                    .collect();
+                Ok::<_, anyhow::Error>(runs)
+            }
+        })
+        .buffered(MAX_CONCURRENT)
+        .try_collect()
+        .await?;
+    let runs: Vec<TestRun> = runs.into_iter().flatten().collect();
+
+    if runs.is_empty() {
+        eprintln!("No runs of {} found", name);
+        return Ok(());
+    }
+    output.print(&runs, &run_columns())?;
+
+    if output.is_table() {
+        match first_failure(&runs) {
+            Some((broke, Some(passed))) => println!(
+                "Failing since pipeline {} ({}), last passed in pipeline {} ({})",
+                broke.pipeline, broke.sha, passed.pipeline, passed.sha
+            ),
+            Some((broke, None)) => println!(
+                "Failing in every pipeline found, since pipeline {} ({})",
+                broke.pipeline, broke.sha
+            ),
+            None => println!("Not failing in the latest pipeline"),
+        }
+    }
+
+    Ok(())
+}
+
+// Start of the current streak of failures, and the last pass before it
+fn first_failure(runs: &[TestRun]) -> Option<(&TestRun, Option<&TestRun>)> {
+    if !runs.last()?.case.is_failure() {
+        return None;
+    }
+    match runs.iter().rposition(|r| r.case.status == "success") {
+        Some(i) => Some((
+            runs[i + 1..].iter().find(|r| r.case.is_failure())?,
+            Some(&runs[i]),
+        )),
+        None => Some((runs.iter().find(|r| r.case.is_failure())?, None)),
+    }
+}
@@ -1,16 +1,26 @@
-use crate::credentials::Credentials;
+use std::io::Read;
+
+use crate::artifacts::ArtifactGlob;
+use crate::client::GitlabClient;
+use crate::commands::get_artifact::get_artifacts_archive;
+use crate::error::GlcError;
+use crate::format::format_seconds;
+use crate::git::current_branch;
+use crate::output::{Column, Output};
+use crate::pipeline::latest_pipeline;
+use crate::test_files::parse_test_file;
 
 use anyhow::Result;
-use reqwest::Url;
-use serde_derive::Deserialize;
+use prettytable::Cell;
+use serde_derive::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct TestReportSummary {
     pub total: TestSummaryDetail,
     pub test_suites: Vec<TestSuiteSummary>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct TestSummaryDetail {
     pub time: f64,
     pub count: u32,
@@ -21,7 +31,7 @@ pub struct TestSummaryDetail {
     pub suite_error: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct TestSuiteSummary {
     pub name: String,
     pub total_time: f64,
@@ -34,40 +44,375 @@ pub struct TestSuiteSummary {
     pub suite_error: Option<String>,
 }
 
-#[allow(dead_code)]
+impl From<TestSummaryDetail> for TestSuiteSummary {
+    // The totals, as a suite named "Total" to go below the others
+    fn from(total: TestSummaryDetail) -> Self {
+        TestSuiteSummary {
+            name: "Total".to_string(),
+            total_time: total.time,
+            total_count: total.count,
+            success_count: total.success,
+            failed_count: total.failed,
+            skipped_count: total.skipped,
+            error_count: total.error,
+            build_ids: Vec::new(),
+            suite_error: total.suite_error,
+        }
+    }
+}
+
+/// Full test report of a pipeline, down to the single test cases
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TestReport {
+    pub total_time: f64,
+    pub total_count: u32,
+    pub success_count: u32,
+    pub failed_count: u32,
+    pub skipped_count: u32,
+    pub error_count: u32,
+    pub test_suites: Vec<TestSuite>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TestSuite {
+    pub name: String,
+    pub total_time: f64,
+    pub total_count: u32,
+    pub success_count: u32,
+    pub failed_count: u32,
+    pub skipped_count: u32,
+    pub error_count: u32,
+    pub suite_error: Option<String>,
+    pub test_cases: Vec<TestCase>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TestCase {
+    pub status: String,
+    pub name: String,
+    pub classname: String,
+    pub execution_time: f64,
+    pub system_output: Option<String>,
+    pub stack_trace: Option<String>,
+}
+
+impl TestSuite {
+    /// A suite of `cases`, with the counts worked out from them
+    pub fn new(name: &str, test_cases: Vec<TestCase>) -> TestSuite {
+        let count = |status: &str| test_cases.iter().filter(|c| c.status == status).count() as u32;
+        TestSuite {
+            name: name.to_string(),
+            total_time: test_cases.iter().map(|c| c.execution_time).sum(),
+            total_count: test_cases.len() as u32,
+            success_count: count("success"),
+            failed_count: count("failed"),
+            skipped_count: count("skipped"),
+            error_count: count("error"),
+            suite_error: None,
+            test_cases,
+        }
+    }
+}
+
+impl TestReport {
+    /// A report of `suites`, with the totals worked out from them
+    pub fn new(test_suites: Vec<TestSuite>) -> TestReport {
+        let sum = |count: fn(&TestSuite) -> u32| test_suites.iter().map(count).sum();
+        TestReport {
+            total_time: test_suites.iter().map(|s| s.total_time).sum(),
+            total_count: sum(|s| s.total_count),
+            success_count: sum(|s| s.success_count),
+            failed_count: sum(|s| s.failed_count),
+            skipped_count: sum(|s| s.skipped_count),
+            error_count: sum(|s| s.error_count),
+            test_suites,
+        }
+    }
+
+    /// The counts only, as the summary endpoint has them
+    pub fn summary(self) -> TestReportSummary {
+        TestReportSummary {
+            total: TestSummaryDetail {
+                time: self.total_time,
+                count: self.total_count,
+                success: self.success_count,
+                failed: self.failed_count,
+                skipped: self.skipped_count,
+                error: self.error_count,
+                suite_error: None,
+            },
+            test_suites: self
+                .test_suites
+                .into_iter()
+                .map(|s| TestSuiteSummary {
+                    name: s.name,
+                    total_time: s.total_time,
+                    total_count: s.total_count,
+                    success_count: s.success_count,
+                    failed_count: s.failed_count,
+                    skipped_count: s.skipped_count,
+                    error_count: s.error_count,
+                    build_ids: Vec::new(),
+                    suite_error: s.suite_error,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TestCase {
+    pub fn is_failure(&self) -> bool {
+        matches!(self.status.as_str(), "failed" | "error")
+    }
+}
+
+/// A failed test case along with the suite it belongs to
+#[derive(Serialize)]
+pub struct FailedTest {
+    pub suite: String,
+    #[serde(flatten)]
+    pub case: TestCase,
+}
+
 pub async fn get_test_report_summary(
-    credentials: &Credentials,
+    client: &GitlabClient,
+    project_id: &str,
+    pipeline_id: usize,
+) -> Result<TestReportSummary> {
+    client
+        .get_json(&format!(
+            "projects/{}/pipelines/{}/test_report_summary",
+            project_id, pipeline_id
+        ))
+        .await
+}
+
+pub async fn get_test_report(
+    client: &GitlabClient,
     project_id: &str,
-    pipeline_id: u32,
-) -> Result<TestReportSummary, Box<dyn std::error::Error>> {
-    let url = format!(
-        "{}/api/v4/projects/{}/pipelines/{}/test_report_summary",
-        credentials.url, project_id, pipeline_id
-    );
-    let url = Url::parse(&url)?;
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(url)
-        .bearer_auth(&credentials.token)
-        .send()
-        .await?;
-
-    // Always capture the response text
-    let response_text = response.text().await?;
-
-    // First, parse the raw response text as a serde_json::Value for potential pretty-printing
-    let json_value: serde_json::Value = serde_json::from_str(&response_text)?;
-
-    // Now attempt to deserialize the serde_json::Value into TestReportSummary
-    serde_json::from_value(json_value.clone()).map_err(|e| {
-        // If deserialization into TestReportSummary fails, pretty-print the original JSON
-        println!("Failed to deserialize TestReportSummary: {}", e);
-        println!(
-            "Original response text as pretty-printed JSON: {}",
-            serde_json::to_string_pretty(&json_value)
-                .unwrap_or_else(|_| "Failed to pretty-print JSON".to_string())
-        );
-        e.into() // Convert serde_json::Error into a Box<dyn std::error::Error>
-    })
+    pipeline_id: usize,
+) -> Result<TestReport> {
+    client
+        .get_json(&format!(
+            "projects/{}/pipelines/{}/test_report",
+            project_id, pipeline_id
+        ))
+        .await
+}
+
+/// The pipeline asked for, or else the latest one on the checked out branch
+pub async fn pipeline_or_latest(
+    client: &GitlabClient,
+    project: &str,
+    pipeline: Option<usize>,
+) -> Result<usize, GlcError> {
+    if let Some(pipeline) = pipeline {
+        return Ok(pipeline);
+    }
+    let branch = current_branch().ok_or_else(|| {
+        GlcError::Config("no pipeline given and no branch checked out, use --pipeline".to_string())
+    })?;
+    let pipeline = latest_pipeline(client, project, Some(&branch), None)
+        .await?
+        .ok_or_else(|| GlcError::NotFound(format!("no pipeline for {}", branch)))?;
+    eprintln!("Pipeline {} ({}) {}", pipeline.id, branch, pipeline.web_url);
+    Ok(pipeline.id as usize)
+}
+
+/// Test time, with sub-second precision where it matters
+pub fn format_test_time(time: f64) -> String {
+    if time < 60.0 {
+        format!("{:.2}s", time)
+    } else {
+        format_seconds(time)
+    }
+}
+
+// Non-zero counts stand out in `colour` (a prettytable style spec)
+fn count_cell(count: u32, colour: &str) -> Cell {
+    let cell = Cell::new(&count.to_string());
+    if count > 0 {
+        cell.style_spec(&format!("r{}", colour))
+    } else {
+        cell.style_spec("r")
+    }
+}
+
+pub fn suite_columns() -> Vec<Column<'static, TestSuiteSummary>> {
+    vec![
+        Column::new("Suite", |s: &TestSuiteSummary| s.name.clone()),
+        Column::new("Total", |s: &TestSuiteSummary| s.total_count.to_string())
+            .human(|s: &TestSuiteSummary| Cell::new(&s.total_count.to_string()).style_spec("r")),
+        Column::new("Passed", |s: &TestSuiteSummary| s.success_count.to_string())
+            .human(|s: &TestSuiteSummary| count_cell(s.success_count, "Fg")),
+        Column::new("Failed", |s: &TestSuiteSummary| s.failed_count.to_string())
+            .human(|s: &TestSuiteSummary| count_cell(s.failed_count, "Fr")),
+        Column::new("Skipped", |s: &TestSuiteSummary| {
+            s.skipped_count.to_string()
+        })
+        .human(|s: &TestSuiteSummary| count_cell(s.skipped_count, "Fy")),
+        Column::new("Errors", |s: &TestSuiteSummary| s.error_count.to_string())
+            .human(|s: &TestSuiteSummary| count_cell(s.error_count, "Fr")),
+        Column::new("Time", |s: &TestSuiteSummary| s.total_time.to_string())
+            .key("duration")
+            .human(|s: &TestSuiteSummary| {
+                Cell::new(&format_test_time(s.total_time)).style_spec("r")
+            }),
+        Column::new("Suite error", |s: &TestSuiteSummary| {
+            s.suite_error.clone().unwrap_or_default()
+        })
+        .human(|s: &TestSuiteSummary| match &s.suite_error {
+            Some(e) => Cell::new(e).style_spec("bFWBr"),
+            None => Cell::new(""),
+        }),
+    ]
+}
+
+// Lines of a stack trace shown in the failure table
+const TRACE_EXCERPT_LINES: usize = 6;
+const TRACE_EXCERPT_WIDTH: usize = 120;
+
+// The start of the stack trace (or the output when there's none), which
+// is usually where the assertion is
+fn trace_excerpt(case: &TestCase) -> String {
+    let trace = case
+        .stack_trace
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .or(case.system_output.as_deref())
+        .unwrap_or_default();
+    strip_ansi_escapes::strip_str(trace)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .take(TRACE_EXCERPT_LINES)
+        .map(|l| l.chars().take(TRACE_EXCERPT_WIDTH).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn failed_columns() -> Vec<Column<'static, FailedTest>> {
+    vec![
+        Column::new("Suite", |f: &FailedTest| f.suite.clone()),
+        Column::new("Class", |f: &FailedTest| f.case.classname.clone()),
+        Column::new("Name", |f: &FailedTest| f.case.name.clone()),
+        Column::new("Status", |f: &FailedTest| f.case.status.clone())
+            .human(|f: &FailedTest| Cell::new(&f.case.status).style_spec("Fr")),
+        Column::new("Time", |f: &FailedTest| f.case.execution_time.to_string())
+            .key("duration")
+            .human(|f: &FailedTest| {
+                Cell::new(&format_test_time(f.case.execution_time)).style_spec("r")
+            }),
+        Column::new("Trace", |f: &FailedTest| trace_excerpt(&f.case)),
+    ]
+}
+
+// Test results from files in a job's artifacts archive matching `pattern`
+async fn artifact_test_report(
+    client: &GitlabClient,
+    project: &str,
+    job: usize,
+    pattern: &str,
+) -> Result<TestReport, GlcError> {
+    let glob = ArtifactGlob::new(pattern)?;
+    let mut archive = get_artifacts_archive(client, project, job).await?;
+
+    let names: Vec<String> = archive
+        .file_names()
+        .filter(|n| glob.matches(n))
+        .map(str::to_string)
+        .collect();
+    if names.is_empty() {
+        return Err(GlcError::NotFound(format!(
+            "no files matching {} in the artifacts of job {}",
+            pattern, job
+        )));
+    }
+
+    let mut suites = Vec::new();
+    for name in names {
+        let mut contents = String::new();
+        archive.by_name(&name)?.read_to_string(&mut contents)?;
+        suites.extend(parse_test_file(&name, &contents)?);
+    }
+    Ok(TestReport::new(suites))
+}
+
+fn print_failures(
+    report: TestReport,
+    suite: Option<&str>,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let failures: Vec<FailedTest> = report
+        .test_suites
+        .into_iter()
+        .filter(|s| suite.is_none_or(|suite| suite == s.name))
+        .flat_map(|s| {
+            let suite = s.name;
+            s.test_cases
+                .into_iter()
+                .filter(TestCase::is_failure)
+                .map(move |case| FailedTest {
+                    suite: suite.clone(),
+                    case,
+                })
+        })
+        .collect();
+    if failures.is_empty() && output.is_table() {
+        println!("No failed tests");
+        return Ok(());
+    }
+    output.print(&failures, &failed_columns())
+}
+
+fn print_summary(
+    summary: TestReportSummary,
+    suite: Option<&str>,
+    output: &Output,
+) -> Result<(), GlcError> {
+    if suite.is_none() && output.print_document(&summary)? {
+        return Ok(());
+    }
+    let suites: Vec<TestSuiteSummary> = summary
+        .test_suites
+        .into_iter()
+        .filter(|s| suite.is_none_or(|suite| suite == s.name))
+        .collect();
+    match suite {
+        None => output.print_with_total(&suites, &summary.total.into(), &suite_columns()),
+        // The totals don't add up to a single suite
+        Some(_) => output.print(&suites, &suite_columns()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn test_report(
+    client: &GitlabClient,
+    project: &str,
+    pipeline: Option<usize>,
+    job: Option<usize>,
+    artifact: Option<String>,
+    suite: Option<String>,
+    failed: bool,
+    output: &Output,
+) -> Result<(), GlcError> {
+    let suite = suite.as_deref();
+
+    // Reports uploaded as plain artifacts are parsed here instead
+    if let (Some(job), Some(artifact)) = (job, artifact) {
+        let report = artifact_test_report(client, project, job, &artifact).await?;
+        return if failed {
+            print_failures(report, suite, output)
+        } else {
+            print_summary(report.summary(), suite, output)
+        };
+    }
+
+    let pipeline = pipeline_or_latest(client, project, pipeline).await?;
+    if failed {
+        let report = get_test_report(client, project, pipeline).await?;
+        print_failures(report, suite, output)
+    } else {
+        let summary = get_test_report_summary(client, project, pipeline).await?;
+        print_summary(summary, suite, output)
+    }
 }
@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use tokio::time::{sleep, timeout};
+
+use crate::client::GitlabClient;
+use crate::error::GlcError;
+use crate::format::{format_seconds, format_status};
+use crate::git::head_commit;
+use crate::job::{get_job_details, is_active};
+use crate::pipeline::{get_pipeline, latest_pipeline};
+
+// Polling starts out quick and slows down while nothing changes
+const MIN_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_INTERVAL: Duration = Duration::from_secs(30);
+
+enum Target {
+    Job(usize),
+    Pipeline(usize),
+    // Newest pipeline for a ref and/or commit, which may not exist yet
+    Latest {
+        rref: Option<String>,
+        sha: Option<String>,
+    },
+}
+
+/// Blocks until the job or pipeline is done, printing every change of
+/// its status. Succeeds only if it ended in success.
+pub async fn wait(
+    client: &GitlabClient,
+    project: &str,
+    job: Option<usize>,
+    pipeline: Option<usize>,
+    rref: Option<String>,
+    sha: Option<String>,
+    max_wait: Option<Duration>,
+) -> Result<(), GlcError> {
+    let target = match (job, pipeline) {
+        (Some(job), _) => Target::Job(job),
+        (_, Some(pipeline)) => Target::Pipeline(pipeline),
+        // After a `git push`, what was pushed is what's checked out
+        _ if rref.is_none() && sha.is_none() => Target::Latest {
+            rref: None,
+            sha: Some(head_commit().ok_or_else(|| {
+                GlcError::Config(
+                    "nothing to wait for, use --job, --pipeline, --ref or --sha".to_string(),
+                )
+            })?),
+        },
+        _ => Target::Latest { rref, sha },
+    };
+
+    let what = match &target {
+        Target::Job(id) => format!("job {}", id),
+        Target::Pipeline(id) => format!("pipeline {}", id),
+        Target::Latest { rref, sha } => format!(
+            "pipeline for {}",
+            sha.as_deref().or(rref.as_deref()).unwrap_or_default()
+        ),
+    };
+
+    match max_wait {
+        Some(max_wait) => timeout(max_wait, poll(client, project, target))
+            .await
+            .map_err(|_| GlcError::Timeout(what))?,
+        None => poll(client, project, target).await,
+    }
+}
+
+async fn poll(client: &GitlabClient, project: &str, mut target: Target) -> Result<(), GlcError> {
+    let start = Instant::now();
+    let mut interval = MIN_INTERVAL;
+    let mut last = None;
+
+    loop {
+        let current = match &target {
+            Target::Job(id) => {
+                let job = get_job_details(client, project, *id).await?;
+                Some((format!("job {}", id), job.status))
+            }
+            Target::Pipeline(id) => {
+                let pipeline = get_pipeline(client, project, *id).await?;
+                Some((format!("pipeline {}", id), pipeline.status))
+            }
+            Target::Latest { rref, sha } => {
+                match latest_pipeline(client, project, rref.as_deref(), sha.as_deref()).await? {
+                    Some(pipeline) => {
+                        println!("Found pipeline {} {}", pipeline.id, pipeline.web_url);
+                        // Stick to it, even if a newer one shows up
+                        target = Target::Pipeline(pipeline.id as usize);
+                        Some((format!("pipeline {}", pipeline.id), pipeline.status))
+                    }
+                    None => None,
+                }
+            }
+        };
+
+        if current != last {
+            interval = MIN_INTERVAL;
+            match &current {
+                Some((what, status)) => println!(
+                    "[{:>10}] {} {}",
+                    format_seconds(start.elapsed().as_secs_f64()),
+                    what,
+                    format_status(status)
+                ),
+                None => println!("Waiting for the pipeline to be created..."),
+            }
+        } else {
+            interval = (interval * 3 / 2).min(MAX_INTERVAL);
+        }
+
+        if let Some((what, status)) = &current {
+            if !is_active(status) {
+                return match status.as_str() {
+                    "success" => Ok(()),
+                    _ => Err(GlcError::Unsuccessful(what.clone(), status.clone())),
+                };
+            }
+        }
+        last = current;
+        sleep(interval).await;
+    }
+}
@@ -0,0 +1,49 @@
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::GlcError;
+
+/// User preferences from `$XDG_CONFIG_HOME/glc/config.yaml` (or
+/// `~/.config/glc/config.yaml`), e.g.
+///
+/// ```yaml
+/// columns:
+///   list-jobs: [id, name, status, duration]
+/// ```
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// Default `--columns` per command
+    #[serde(default)]
+    pub columns: HashMap<String, Vec<String>>,
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config_dir.join("glc").join("config.yaml"))
+}
+
+/// glc's own directory under `$XDG_CACHE_HOME` (or `~/.cache`)
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(cache_dir.join("glc"))
+}
+
+pub fn load_config() -> Result<Config, GlcError> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e.into()),
+    };
+    serde_yaml::from_str(&contents)
+        .map_err(|e| GlcError::Config(format!("Failed to parse {}: {}", path.display(), e)))
+}
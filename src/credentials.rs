@@ -1,7 +1,13 @@
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Write};
+
+use crate::error::GlcError;
+
+// Profile name used for the pre-profiles single-entry file format
+const LEGACY_PROFILE: &str = "default";
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Credentials {
@@ -10,26 +16,105 @@ pub struct Credentials {
     pub project: Option<String>,
 }
 
-pub fn load_credentials() -> std::io::Result<Credentials> {
+/// Contents of `~/.creds`: a set of named profiles and the one in use
+/// when neither `--profile` nor `GLC_PROFILE` picks another.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Profiles {
+    pub default: Option<String>,
+    pub profiles: BTreeMap<String, Credentials>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CredentialsFile {
+    Profiles(Profiles),
+    Legacy(Credentials),
+}
+
+fn creds_path() -> std::io::Result<String> {
     let home_dir = env::var("HOME").map_err(|_| {
         std::io::Error::new(
             std::io::ErrorKind::NotFound,
             "Could not find home directory",
         )
     })?;
-    let creds_path = format!("{}/.creds", home_dir);
-    let file = File::open(&creds_path).map_err(|_| {
-        std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Failed to open credentials file at {}", creds_path),
-        )
-    })?;
-    let reader = BufReader::new(file);
-    let creds: Credentials = serde_yaml::from_reader(reader).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Failed to parse credentials file: {}", e),
+    Ok(format!("{}/.creds", home_dir))
+}
+
+impl Profiles {
+    /// Loads the credentials file. A missing file is an empty set of
+    /// profiles; a file in the old single-profile format is read as a
+    /// profile named "default".
+    pub fn load() -> std::io::Result<Profiles> {
+        let creds_path = creds_path()?;
+        let file = match File::open(&creds_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Profiles::default()),
+            Err(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Failed to open credentials file at {}", creds_path),
+                ))
+            }
+        };
+        let reader = BufReader::new(file);
+        let creds: CredentialsFile = serde_yaml::from_reader(reader).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to parse credentials file: {}", e),
+            )
+        })?;
+
+        Ok(match creds {
+            CredentialsFile::Profiles(profiles) => profiles,
+            CredentialsFile::Legacy(creds) => Profiles {
+                default: Some(LEGACY_PROFILE.to_string()),
+                profiles: BTreeMap::from([(LEGACY_PROFILE.to_string(), creds)]),
+            },
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let creds_string = serde_yaml::to_string(self)
+            .map_err(|_| std::io::Error::other("Failed to serialize credentials"))?;
+        let creds_path = creds_path()?;
+        let mut file = File::create(&creds_path).map_err(|_| {
+            std::io::Error::other(format!(
+                "Failed to create credentials file at {}",
+                creds_path
+            ))
+        })?;
+        file.write_all(creds_string.as_bytes())
+    }
+
+    /// Name of the profile to use: the explicit one if given, otherwise
+    /// the default, otherwise the only one there is.
+    pub fn selected(&self, profile: Option<&str>) -> Option<String> {
+        match (profile, &self.default) {
+            (Some(p), _) => Some(p.to_string()),
+            (None, Some(d)) => Some(d.clone()),
+            (None, None) if self.profiles.len() == 1 => self.profiles.keys().next().cloned(),
+            (None, None) => None,
+        }
+    }
+}
+
+pub fn load_credentials(profile: Option<&str>) -> Result<Credentials, GlcError> {
+    let profiles = Profiles::load()?;
+    if profiles.profiles.is_empty() {
+        return Err(GlcError::Config(
+            "no credentials found, run `glc login` first".to_string(),
+        ));
+    }
+
+    let name = profiles.selected(profile).ok_or_else(|| {
+        GlcError::Config(
+            "several profiles configured, pick one with --profile or `glc profile use`".to_string(),
         )
     })?;
-    Ok(creds)
+    profiles
+        .profiles
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| GlcError::Config(format!("no profile named {:?}", name)))
 }
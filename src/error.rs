@@ -0,0 +1,137 @@
+use reqwest::{Response, StatusCode};
+use serde_derive::Deserialize;
+use thiserror::Error;
+
+/// Errors surfaced to the user. Every variant maps to its own process
+/// exit code so scripts can tell them apart.
+#[derive(Error, Debug)]
+pub enum GlcError {
+    #[error("authentication failed, the token is invalid or expired (run `glc login`)")]
+    Auth,
+    #[error("token lacks {0}")]
+    MissingScope(String),
+    #[error("permission denied: {0}")]
+    Forbidden(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("rate limited by GitLab, try again later")]
+    RateLimited,
+    #[error("GitLab server error: {0}")]
+    Server(StatusCode),
+    #[error("unexpected response from GitLab: {0}")]
+    Schema(String),
+    #[error("{0}")]
+    Config(String),
+    /// A job or pipeline that was waited for ended in `status`
+    #[error("{0} finished with status {1}")]
+    Unsuccessful(String, String),
+    #[error("timed out waiting for {0}")]
+    Timeout(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl GlcError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GlcError::Other(_) => 1,
+            // 2 is taken by clap for usage errors
+            GlcError::Auth => 3,
+            GlcError::MissingScope(_) => 4,
+            GlcError::Forbidden(_) => 5,
+            GlcError::NotFound(_) => 6,
+            GlcError::RateLimited => 7,
+            GlcError::Server(_) => 8,
+            GlcError::Schema(_) => 9,
+            GlcError::Io(_) => 10,
+            GlcError::Config(_) => 11,
+            GlcError::Unsuccessful(_, status) => match status.as_str() {
+                "failed" => 12,
+                "canceled" => 13,
+                _ => 14,
+            },
+            GlcError::Timeout(_) => 15,
+        }
+    }
+}
+
+// Errors from the library modules travel as anyhow::Error, so pull the
+// typed error back out if there is one underneath
+impl From<anyhow::Error> for GlcError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<GlcError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<reqwest::Error>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<serde_json::Error>() {
+            Ok(e) => e.into(),
+            Err(e) => GlcError::Other(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for GlcError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            GlcError::Schema(e.to_string())
+        } else {
+            GlcError::Other(e.into())
+        }
+    }
+}
+
+impl From<serde_json::Error> for GlcError {
+    fn from(e: serde_json::Error) -> Self {
+        GlcError::Schema(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for GlcError {
+    fn from(e: zip::result::ZipError) -> Self {
+        GlcError::Other(e.into())
+    }
+}
+
+// Error body GitLab sends along with 4xx responses
+#[derive(Deserialize, Default)]
+struct ErrorBody {
+    message: Option<serde_json::Value>,
+    error: Option<String>,
+    scope: Option<String>,
+}
+
+/// Turns an unsuccessful response into the matching `GlcError`.
+pub async fn check_status(response: Response) -> Result<Response, GlcError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let path = response.url().path().to_string();
+    let body: ErrorBody = response.json().await.unwrap_or_default();
+    let message = match body.message {
+        Some(serde_json::Value::String(s)) => s,
+        Some(v) => v.to_string(),
+        None => status.to_string(),
+    };
+
+    Err(match status {
+        StatusCode::UNAUTHORIZED => GlcError::Auth,
+        StatusCode::FORBIDDEN if body.error.as_deref() == Some("insufficient_scope") => {
+            // GitLab lists every scope that would have been accepted
+            let scope = body.scope.unwrap_or_else(|| "the required scope".into());
+            GlcError::MissingScope(scope.split_whitespace().collect::<Vec<_>>().join(" or "))
+        }
+        StatusCode::FORBIDDEN => GlcError::Forbidden(format!("{} ({})", message, path)),
+        StatusCode::NOT_FOUND => GlcError::NotFound(format!("{} ({})", message, path)),
+        StatusCode::TOO_MANY_REQUESTS => GlcError::RateLimited,
+        s if s.is_server_error() => GlcError::Server(s),
+        _ => GlcError::Other(anyhow::anyhow!("{} ({})", message, path)),
+    })
+}
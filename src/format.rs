@@ -56,3 +56,13 @@ pub fn format_seconds(sec: f64) -> String {
         format!("{:.2}s", sec)
     }
 }
+
+pub fn format_status(status: &str) -> ColoredString {
+    match status {
+        "success" => "✅\u{00a0} Success".green(),
+        "failed" => "❌\u{00a0} Failed".red(),
+        "running" => "⏳\u{00a0} Running".yellow(),
+        "created" => "🌱\u{00a0} Created".normal(),
+        stat => format!("❓\u{00a0} {stat}").normal(),
+    }
+}
@@ -0,0 +1,149 @@
+use std::process::Command;
+
+use reqwest::Url;
+
+/// Project path (`group/sub/project`) of the current repository on the
+/// GitLab instance at `gitlab_url`, taken from the first remote that
+/// points there. `origin` is tried first.
+pub fn remote_project_path(gitlab_url: &Url) -> Option<String> {
+    let remotes = git(&["remote"])?;
+    let mut remotes: Vec<&str> = remotes.lines().collect();
+    remotes.sort_by_key(|r| *r != "origin");
+
+    remotes
+        .into_iter()
+        .filter_map(|r| git(&["remote", "get-url", r]))
+        .find_map(|url| project_path(url.trim(), gitlab_url))
+}
+
+/// Commit checked out in the current repository.
+pub fn head_commit() -> Option<String> {
+    git(&["rev-parse", "HEAD"]).map(|sha| sha.trim().to_string())
+}
+
+/// Branch checked out in the current repository, None when detached.
+pub fn current_branch() -> Option<String> {
+    let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let branch = branch.trim();
+    (branch != "HEAD").then(|| branch.to_string())
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Project path from a remote or web URL, if it is on `gitlab_url`'s host.
+pub fn project_path(remote: &str, gitlab_url: &Url) -> Option<String> {
+    // URL style (https://, ssh://) or scp style (git@host:group/project)
+    let (host, path, http) = match Url::parse(remote).ok().filter(|u| u.has_host()) {
+        Some(url) => (
+            url.host_str()?.to_string(),
+            url.path().to_string(),
+            matches!(url.scheme(), "http" | "https"),
+        ),
+        None => {
+            let (user_host, path) = remote.split_once(':')?;
+            let host = user_host.rsplit('@').next()?;
+            (host.to_string(), path.to_string(), false)
+        }
+    };
+    if host != gitlab_url.host_str()? {
+        return None;
+    }
+
+    let mut path = path.trim_matches('/');
+    // Instances served from a subdirectory have it in their HTTP remotes
+    // and web URLs, but not in SSH ones
+    let prefix = gitlab_url.path().trim_matches('/');
+    if http && !prefix.is_empty() {
+        if let Some(p) = path.strip_prefix(&format!("{}/", prefix)) {
+            path = p;
+        }
+    }
+    // Web URLs of pages within a project, e.g. .../project/-/pipelines/1
+    let path = path.split("/-/").next().unwrap_or(path);
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    (!path.is_empty()).then(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(remote: &str, gitlab_url: &str) -> Option<String> {
+        project_path(remote, &Url::parse(gitlab_url).unwrap())
+    }
+
+    #[test]
+    fn scp_style() {
+        let gitlab = "https://gitlab.example.com/";
+        assert_eq!(
+            path("git@gitlab.example.com:group/sub/app.git", gitlab).as_deref(),
+            Some("group/sub/app")
+        );
+        assert_eq!(
+            path("gitlab.example.com:app", gitlab).as_deref(),
+            Some("app")
+        );
+        assert_eq!(path("git@github.com:group/app.git", gitlab), None);
+    }
+
+    #[test]
+    fn ssh_with_port() {
+        assert_eq!(
+            path(
+                "ssh://git@gitlab.example.com:2222/group/app.git",
+                "https://gitlab.example.com/"
+            )
+            .as_deref(),
+            Some("group/app")
+        );
+    }
+
+    #[test]
+    fn https_in_subdirectory() {
+        let gitlab = "https://h.example.com/gitlab/";
+        assert_eq!(
+            path("https://h.example.com/gitlab/group/app.git", gitlab).as_deref(),
+            Some("group/app")
+        );
+        // SSH remotes don't have the subdirectory, only HTTP ones do
+        assert_eq!(
+            path("git@h.example.com:gitlab-tools/app.git", gitlab).as_deref(),
+            Some("gitlab-tools/app")
+        );
+        assert_eq!(
+            path("git@h.example.com:gitlab/app.git", gitlab).as_deref(),
+            Some("gitlab/app")
+        );
+        assert_eq!(
+            path("https://h.example.com/gitlab/gitlab-tools/app", gitlab).as_deref(),
+            Some("gitlab-tools/app")
+        );
+    }
+
+    #[test]
+    fn web_urls() {
+        assert_eq!(
+            path(
+                "https://gitlab.example.com/group/app/-/pipelines/1234",
+                "https://gitlab.example.com"
+            )
+            .as_deref(),
+            Some("group/app")
+        );
+        assert_eq!(
+            path(
+                "https://h.example.com/gitlab/group/app/-/pipelines/1234",
+                "https://h.example.com/gitlab"
+            )
+            .as_deref(),
+            Some("group/app")
+        );
+    }
+}
@@ -1,43 +1,113 @@
-use clap::{ArgAction, Parser};
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser};
 use parse_duration::parse;
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
 
+mod artifacts;
+mod client;
 mod commands {
     pub mod cancel_job;
+    pub mod create_pipeline;
     pub mod get_artifact;
     pub mod job_history;
+    pub mod list_artifacts;
     pub mod list_jobs;
     pub mod list_pipelines;
     pub mod list_projects;
     pub mod list_runners;
     pub mod login;
+    pub mod pipeline_action;
+    pub mod play_job;
+    pub mod profile;
+    pub mod prune_pipelines;
+    pub mod retry_job;
     pub mod show_job;
+    pub mod slowest_tests;
+    pub mod test_diff;
+    pub mod test_history;
     pub mod test_report;
+    pub mod wait;
 }
+mod config;
 mod credentials;
+mod error;
 mod format;
+mod git;
 mod job;
+mod output;
+mod pagination;
 mod pipeline;
 mod project;
+mod prompt;
 mod runner;
+mod test_files;
+mod trace;
 
+use client::GitlabClient;
 use commands::cancel_job::cancel_job;
+use commands::create_pipeline::create_pipeline;
 use commands::get_artifact::get_artifact;
 use commands::job_history::job_history;
+use commands::list_artifacts::list_artifacts;
 use commands::list_jobs::list_jobs;
 use commands::list_pipelines::list_pipelines;
 use commands::list_projects::list_projects;
 use commands::list_runners::list_runners;
 use commands::login::login;
+use commands::pipeline_action::{pipeline_action, PipelineAction};
+use commands::play_job::play_job;
+use commands::profile::{list_profiles, remove_profile, use_profile};
+use commands::prune_pipelines::prune_pipelines;
+use commands::retry_job::retry_job;
 use commands::show_job::show_job;
+use commands::slowest_tests::{slowest_tests, RankBy};
+use commands::test_diff::test_diff;
+use commands::test_history::test_history;
+use commands::test_report::test_report;
+use commands::wait::wait;
+use config::load_config;
 use credentials::load_credentials;
+use error::GlcError;
+use output::{Output, OutputFormat};
+use project::resolve_project;
 
 #[derive(Parser, Debug)]
 #[clap(name = "glc", about = "gitlab client utility")]
 struct Opt {
-    /// The project ID
-    #[clap(short = 'P', long = "project", default_value = "197")]
-    project: String,
+    /// Project ID, path (group/project) or web URL (defaults to the git
+    /// remote's project, then the profile's)
+    #[clap(short = 'P', long = "project", env = "GITLAB_PROJECT", global = true)]
+    project: Option<String>,
+
+    /// Output format
+    #[clap(
+        short = 'o',
+        long = "output",
+        value_enum,
+        default_value_t = OutputFormat::Table,
+        global = true
+    )]
+    output: OutputFormat,
+
+    /// Columns to show, comma separated (defaults to the config file's
+    /// `columns` entry for the command)
+    #[clap(long = "columns", value_delimiter = ',', global = true)]
+    columns: Option<Vec<String>>,
+
+    /// Columns to sort by, comma separated, `-` prefix for descending
+    #[clap(
+        long = "sort-by",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        global = true
+    )]
+    sort_by: Vec<String>,
+
+    /// Credentials profile to use
+    #[clap(long = "profile", env = "GLC_PROFILE", global = true)]
+    profile: Option<String>,
 
     #[command(subcommand)]
     cmd: Command,
@@ -53,6 +123,10 @@ enum Command {
         url: String,
     },
 
+    /// Manage credential profiles
+    #[command(name = "profile", subcommand)]
+    Profile(ProfileCommand),
+
     /// List jobs
     #[command(name = "list-jobs")]
     ListJobs {
@@ -89,11 +163,25 @@ enum Command {
         /// Job ID to download from
         #[clap(short = 'j', long = "job")]
         job: usize,
-        /// Artifact name
-        #[clap(short = 'n', long = "name")]
-        name: String,
+        /// Artifact path, or a glob of paths ("target/**/*.log"). Can be
+        /// given more than once.
+        #[clap(short = 'n', long = "name", required_unless_present = "all")]
+        names: Vec<String>,
+        /// Extract every file in the artifacts
+        #[clap(short = 'a', long = "all", conflicts_with = "names")]
+        all: bool,
+        /// Directory to extract into, keeping the paths within the artifacts
+        #[clap(short = 'd', long = "output-dir", default_value = ".")]
+        output_dir: PathBuf,
+        /// Overwrite files that already exist
+        #[clap(short = 'f', long = "force")]
+        force: bool,
     },
 
+    /// Look into job artifacts
+    #[command(name = "artifact", subcommand)]
+    Artifact(ArtifactCommand),
+
     /// Show historical results for a job (by name)
     #[command(name = "job-history")]
     JobHistory {
@@ -125,6 +213,125 @@ enum Command {
         rref: Option<String>,
     },
 
+    /// Create (trigger) a pipeline
+    #[command(name = "create-pipeline")]
+    CreatePipeline {
+        /// Reference (branch or tag) to run for, defaults to the checked
+        /// out branch
+        #[clap(short = 'r', long = "ref")]
+        rref: Option<String>,
+        /// Pipeline variable (KEY=VALUE), can be repeated
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_variable)]
+        variables: Vec<(String, String)>,
+        /// File type pipeline variable with the contents of a local file
+        /// (KEY=PATH), can be repeated
+        #[clap(long = "file-var", value_name = "KEY=PATH", value_parser = parse_variable)]
+        file_variables: Vec<(String, String)>,
+        /// Wait for the pipeline to finish, exiting with its status
+        #[clap(short = 'w', long = "watch")]
+        watch: bool,
+    },
+
+    /// Retry the failed jobs of pipelines
+    #[command(name = "retry-pipeline")]
+    RetryPipeline(PipelineSelection),
+
+    /// Cancel pipelines
+    #[command(name = "cancel-pipeline")]
+    CancelPipeline(PipelineSelection),
+
+    /// Delete old pipelines, along with their jobs and artifacts
+    #[command(name = "prune-pipelines")]
+    PrunePipelines {
+        /// Only pipelines last updated longer ago than this ("30d", "12w" etc)
+        #[clap(long = "older-than", default_value = "30d")]
+        older_than: String,
+        /// Source (type of pipeline)
+        #[clap(short = 's', long = "source")]
+        source: Option<String>,
+        /// Reference (branch)
+        #[clap(short = 'r', long = "ref")]
+        rref: Option<String>,
+        /// Status ("failed", "canceled", "success" etc)
+        #[clap(long = "status")]
+        status: Option<String>,
+        /// Don't ask for confirmation
+        #[clap(short = 'y', long = "yes")]
+        yes: bool,
+        /// Only show what would be deleted
+        #[clap(long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Show the test report of a pipeline
+    #[command(name = "test-report")]
+    TestReport {
+        /// Pipeline ID (defaults to the latest on the checked out branch)
+        #[clap(short = 'p', long = "pipeline", conflicts_with = "job")]
+        pipeline: Option<usize>,
+        /// Job ID to read test results from artifacts of, instead
+        #[clap(short = 'j', long = "job", requires = "artifact")]
+        job: Option<usize>,
+        /// Artifact files with the test results (JUnit, xUnit or libtest
+        /// JSON), as a glob ("reports/*.xml")
+        #[clap(short = 'a', long = "artifact", requires = "job")]
+        artifact: Option<String>,
+        /// Only this test suite
+        #[clap(long = "suite")]
+        suite: Option<String>,
+        /// List the failed tests, with the start of their stack trace
+        #[clap(long = "failed")]
+        failed: bool,
+        /// Rank the N slowest tests of the pipelines within --max-age
+        #[clap(long = "slowest", conflicts_with_all = ["pipeline", "job", "failed"])]
+        slowest: Option<usize>,
+        /// Time to rank --slowest by
+        #[clap(long = "by", value_enum, default_value = "mean", requires = "slowest")]
+        by: RankBy,
+        /// Rank suites instead of tests
+        #[clap(long = "suites", requires = "slowest")]
+        suites: bool,
+        /// Max history for --slowest ("1h", "10m", "4d" etc), compared
+        /// against the same length of time before it
+        #[clap(short = 'm', default_value = "7d", long = "max-age")]
+        max_age: String,
+        /// Source (type of pipeline) for --slowest
+        #[clap(short = 's', long = "source", requires = "slowest")]
+        source: Option<String>,
+        /// Reference (branch) for --slowest
+        #[clap(short = 'r', long = "ref", requires = "slowest")]
+        rref: Option<String>,
+    },
+
+    /// Show historical results for a test (by name)
+    #[command(name = "test-history")]
+    TestHistory {
+        /// Test name, optionally prefixed with its class ("class.name")
+        #[clap(short = 'n', long = "name")]
+        name: String,
+        /// Only this test suite
+        #[clap(long = "suite")]
+        suite: Option<String>,
+        /// Max history ("1h", "10m", "4d" etc)
+        #[clap(short = 'm', default_value = "7d", long = "max-age")]
+        max_age: String,
+        /// Source (type of pipeline)
+        #[clap(short = 's', long = "source")]
+        source: Option<String>,
+        /// Reference (branch)
+        #[clap(short = 'r', long = "ref")]
+        rref: Option<String>,
+    },
+
+    /// Compare the test results of two pipelines
+    #[command(name = "test-diff")]
+    TestDiff {
+        /// Pipeline ID to compare against (e.g. on the main branch)
+        base: usize,
+        /// Pipeline ID to compare (e.g. of a merge request)
+        head: usize,
+    },
+
     /// Cancel job
     #[command(name = "cancel-job")]
     CancelJob {
@@ -143,6 +350,153 @@ enum Command {
         )]
         names: Option<Vec<String>>,
     },
+
+    /// Play (start) manual job
+    #[command(name = "play-job")]
+    PlayJob {
+        /// The ID of the job(s) to play
+        #[clap(
+            conflicts_with = "pipeline",
+            required_unless_present = "pipeline",
+            use_value_delimiter = true
+        )]
+        jobs: Option<Vec<usize>>,
+        /// Pipeline ID to play manual job(s) in
+        #[clap(
+            short = 'p',
+            long = "pipeline",
+            conflicts_with = "jobs",
+            requires = "names"
+        )]
+        pipeline: Option<usize>,
+        /// Name of job(s) to play
+        #[clap(
+            short = 'n',
+            long = "name",
+            conflicts_with = "jobs",
+            use_value_delimiter = true
+        )]
+        names: Option<Vec<String>>,
+        /// Job variable (KEY=VALUE), can be repeated
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_variable)]
+        variables: Vec<(String, String)>,
+        /// Follow the log of the started job
+        #[clap(short = 'f', long = "follow")]
+        follow: bool,
+    },
+
+    /// Wait for a job or pipeline to finish, exiting with its status
+    #[command(name = "wait")]
+    Wait {
+        /// Job ID to wait for
+        #[clap(short = 'j', long = "job", conflicts_with_all = ["pipeline", "rref", "sha"])]
+        job: Option<usize>,
+        /// Pipeline ID to wait for
+        #[clap(short = 'p', long = "pipeline", conflicts_with_all = ["rref", "sha"])]
+        pipeline: Option<usize>,
+        /// Wait for the latest pipeline on this reference (branch)
+        #[clap(short = 'r', long = "ref")]
+        rref: Option<String>,
+        /// Wait for the latest pipeline for this commit (defaults to the
+        /// checked out commit when nothing else is given)
+        #[clap(long = "sha")]
+        sha: Option<String>,
+        /// Give up after this long ("30m", "2h" etc)
+        #[clap(short = 't', long = "timeout")]
+        timeout: Option<String>,
+    },
+
+    /// Retry job
+    #[command(name = "retry-job")]
+    RetryJob {
+        /// The ID of the job(s) to retry
+        #[clap(
+            conflicts_with = "pipeline",
+            required_unless_present = "pipeline",
+            use_value_delimiter = true
+        )]
+        jobs: Option<Vec<usize>>,
+        /// Pipeline ID to retry job(s) in
+        #[clap(short = 'p', long = "pipeline", conflicts_with = "jobs")]
+        pipeline: Option<usize>,
+        /// Name of job(s) to retry
+        #[clap(
+            short = 'n',
+            long = "name",
+            conflicts_with = "jobs",
+            use_value_delimiter = true
+        )]
+        names: Option<Vec<String>>,
+        /// Only retry jobs with this status ("failed", "canceled", etc).
+        /// With --pipeline, defaults to the failed and canceled ones.
+        #[clap(short = 's', long = "status")]
+        status: Option<String>,
+        /// Only retry jobs that failed for one of these reasons
+        /// ("runner_system_failure", "stuck_or_timeout_failure", etc)
+        #[clap(long = "failure-reason", use_value_delimiter = true)]
+        failure_reasons: Option<Vec<String>>,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum ProfileCommand {
+    /// List profiles, marking the one in use
+    #[command(name = "list")]
+    List,
+    /// Make a profile the default
+    #[command(name = "use")]
+    Use {
+        /// Profile name
+        name: String,
+    },
+    /// Remove a profile
+    #[command(name = "remove")]
+    Remove {
+        /// Profile name
+        name: String,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum ArtifactCommand {
+    /// List the files in the artifacts of a job, with their sizes
+    #[command(name = "ls")]
+    List {
+        /// Job ID to list the artifacts of
+        #[clap(short = 'j', long = "job")]
+        job: usize,
+    },
+}
+
+/// Pipelines to act on: given by ID, or picked among the recent ones
+#[derive(Parser, Debug)]
+pub struct PipelineSelection {
+    /// The ID of the pipeline(s)
+    #[clap(
+        use_value_delimiter = true,
+        conflicts_with_all = ["rref", "superseded"],
+        required_unless_present_any = ["rref", "superseded"]
+    )]
+    pipelines: Vec<usize>,
+    /// Pick the pipelines on this reference (branch)
+    #[clap(short = 'r', long = "ref")]
+    rref: Option<String>,
+    /// Pick all but the newest pipeline of every reference
+    #[clap(long = "superseded")]
+    superseded: bool,
+    /// Status to pick ("running", "failed", etc), defaults to any the
+    /// action applies to
+    #[clap(short = 's', long = "status")]
+    status: Option<String>,
+    /// Max history to pick from ("1h", "10m", "4d" etc)
+    #[clap(short = 'm', default_value = "24h", long = "max-age")]
+    max_age: String,
+    /// Don't ask for confirmation
+    #[clap(short = 'y', long = "yes")]
+    yes: bool,
+    /// Only show what would be done
+    #[clap(long = "dry-run")]
+    dry_run: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -156,9 +510,10 @@ pub struct ShowJobArgs {
     /// Status summary after output
     #[clap(long = "no-status", action = ArgAction::SetFalse)]
     status: bool,
-    /// Follow (keep listening)
-    #[clap(short = 'f', long = "follow", requires = "job")]
-    _follow: Option<bool>,
+    /// Follow the log until the job finishes, exiting with its status.
+    /// With --pipeline, follows every job of the pipeline as it runs
+    #[clap(short = 'f', long = "follow")]
+    follow: bool,
     /// Number of lines of output to show (negative number)
     #[clap(short = 't', long = "tail")]
     tail: Option<usize>,
@@ -178,80 +533,312 @@ impl ShowJobArgs {
         if self.job.is_none() && self.pipeline.is_none() {
             return Err(String::from("Must specify either job or pipeline."));
         }
-        if let Some(_) = self.pipeline {
-            if self.status {
-                self.status = false; // default for pipeline
-            } else if self.status {
-                return Err(String::from("Cannot use status with pipeline."));
-            }
+        if self.pipeline.is_some() {
+            self.status = false; // default for pipeline
         }
         Ok(())
     }
+
+    /// Arguments for following a single job's log to the end
+    fn follow(job: usize) -> ShowJobArgs {
+        ShowJobArgs {
+            job: Some(job),
+            pipeline: None,
+            status: true,
+            follow: true,
+            tail: None,
+            stats: false,
+            prefix: false,
+            plain: false,
+        }
+    }
+}
+
+// Parses KEY=VALUE pairs given to --var and --file-var
+fn parse_variable(var: &str) -> Result<(String, String), String> {
+    let (key, value) = var
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", var))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn parse_timeout(timeout: &str) -> Result<Duration, GlcError> {
+    parse(timeout)
+        .map_err(|e| GlcError::Other(anyhow::anyhow!("invalid timeout {:?}: {}", timeout, e)))
+}
+
+fn parse_max_age(max_age: &str) -> Result<isize, GlcError> {
+    parse(max_age)
+        .map(|d| d.as_secs() as isize)
+        .map_err(|e| GlcError::Other(anyhow::anyhow!("invalid max age {:?}: {}", max_age, e)))
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let opt = Opt::parse();
-
-    let project = opt.project;
-    /*
-    println!("repo path: {:#?}", project);
-    let repo = gix::discover(project.clone())?;
-    println!("repo: {:#?}", repo);
-    let remote = repo.find_default_remote(gix::remote::Direction::Push);
-    println!("remote: {:#?}", remote);
-    */
-    /*
-    let project: String = if true {
-        let repo_path = Path::new("."); // path to your git repository
-        match Repository::discover(repo_path) {
-            Ok(repo) => match repo.find_remote("origin") {
-                Ok(remote) => remote.url().unwrap_or("No URL").to_string(),
-                Err(e) => format!("Error: {}", e),
-            },
-            Err(e) => format!("Error: {}", e),
-        }
-    } else {
-        opt.project.unwrap().to_string()
-    };
-    println!("Project: {:?}", project);
-    */
-    if let Command::Login { url } = opt.cmd {
-        login(&url)?;
-        return Ok(());
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("Error: {}", err);
+        std::process::exit(err.exit_code());
     }
+}
 
-    let creds = load_credentials()?;
+async fn run() -> Result<(), GlcError> {
+    let matches = Opt::command().get_matches();
+    let opt = Opt::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
+    let profile = opt.profile.as_deref();
     match opt.cmd {
         Command::Login { url } => {
-            login(&url)?;
+            // Only an explicit -P, not $GITLAB_PROJECT, becomes the
+            // profile's default project
+            let project = match matches.value_source("project") {
+                Some(ValueSource::CommandLine) => opt.project.as_deref(),
+                _ => None,
+            };
+            return login(&url, profile, project);
+        }
+        Command::Profile(cmd) => {
+            return match cmd {
+                ProfileCommand::List => list_profiles(profile),
+                ProfileCommand::Use { name } => use_profile(&name),
+                ProfileCommand::Remove { name } => remove_profile(&name),
+            };
         }
+        _ => {}
+    }
+
+    let creds = load_credentials(profile)?;
+    let client = GitlabClient::new(&creds);
+    let columns = match opt.columns {
+        Some(columns) => Some(columns),
+        None => {
+            let mut config = load_config()?;
+            matches
+                .subcommand_name()
+                .and_then(|cmd| config.columns.remove(cmd))
+        }
+    };
+    let output = Output {
+        format: opt.output,
+        columns,
+        sort_by: opt.sort_by,
+    };
+    // Only commands that need a project pay for resolving it
+    let project = || resolve_project(&client, &creds, opt.project.clone());
+
+    match opt.cmd {
+        Command::Login { .. } | Command::Profile(_) => unreachable!(),
         Command::ListJobs {
             pipelines,
             max_age,
             status,
         } => {
-            let max_age = parse(&max_age)?.as_secs() as isize;
+            let max_age = parse_max_age(&max_age)?;
             let pipelines = pipelines.unwrap_or_else(Vec::new);
-            list_jobs(&creds, &project, pipelines, max_age, status).await?;
+            list_jobs(
+                &client,
+                &project().await?,
+                pipelines,
+                max_age,
+                status,
+                &output,
+            )
+            .await?;
         }
         Command::ShowJob(mut args) => {
             if let Err(err) = args.validate() {
                 eprintln!("Error: {}", err);
                 std::process::exit(1);
             }
-            show_job(&creds, &project, &args).await?;
+            show_job(&client, &project().await?, &args, &output).await?;
+        }
+        Command::CreatePipeline {
+            rref,
+            variables,
+            file_variables,
+            watch,
+        } => {
+            create_pipeline(
+                &client,
+                &project().await?,
+                rref,
+                variables,
+                file_variables,
+                watch,
+                &output,
+            )
+            .await?;
+        }
+        Command::RetryPipeline(args) => {
+            let project = project().await?;
+            pipeline_action(&client, &project, PipelineAction::Retry, &args, &output).await?;
+        }
+        Command::CancelPipeline(args) => {
+            let project = project().await?;
+            pipeline_action(&client, &project, PipelineAction::Cancel, &args, &output).await?;
+        }
+        Command::PrunePipelines {
+            older_than,
+            source,
+            rref,
+            status,
+            yes,
+            dry_run,
+        } => {
+            let older_than = parse_max_age(&older_than)?;
+            prune_pipelines(
+                &client,
+                &project().await?,
+                older_than,
+                source,
+                rref,
+                status,
+                yes,
+                dry_run,
+                &output,
+            )
+            .await?;
+        }
+        Command::TestReport {
+            suite,
+            slowest: Some(count),
+            by,
+            suites,
+            max_age,
+            source,
+            rref,
+            ..
+        } => {
+            slowest_tests(
+                &client,
+                &project().await?,
+                count,
+                by,
+                suites,
+                suite,
+                parse_max_age(&max_age)?,
+                source,
+                rref,
+                &output,
+            )
+            .await?;
+        }
+        Command::TestReport {
+            pipeline,
+            job,
+            artifact,
+            suite,
+            failed,
+            ..
+        } => {
+            test_report(
+                &client,
+                &project().await?,
+                pipeline,
+                job,
+                artifact,
+                suite,
+                failed,
+                &output,
+            )
+            .await?;
+        }
+        Command::TestHistory {
+            name,
+            suite,
+            max_age,
+            source,
+            rref,
+        } => {
+            let max_age = parse_max_age(&max_age)?;
+            test_history(
+                &client,
+                &project().await?,
+                &name,
+                suite,
+                max_age,
+                source,
+                rref,
+                &output,
+            )
+            .await?;
+        }
+        Command::TestDiff { base, head } => {
+            test_diff(&client, &project().await?, base, head, &output).await?;
         }
         Command::CancelJob {
             jobs,
             pipeline,
             names,
         } => {
-            cancel_job(&creds, &project, jobs, pipeline, names).await?;
+            cancel_job(&client, &project().await?, jobs, pipeline, names).await?;
+        }
+        Command::PlayJob {
+            jobs,
+            pipeline,
+            names,
+            variables,
+            follow,
+        } => {
+            play_job(
+                &client,
+                &project().await?,
+                jobs,
+                pipeline,
+                names,
+                variables,
+                follow,
+                &output,
+            )
+            .await?;
+        }
+        Command::Wait {
+            job,
+            pipeline,
+            rref,
+            sha,
+            timeout,
+        } => {
+            let timeout = timeout.as_deref().map(parse_timeout).transpose()?;
+            wait(
+                &client,
+                &project().await?,
+                job,
+                pipeline,
+                rref,
+                sha,
+                timeout,
+            )
+            .await?;
+        }
+        Command::RetryJob {
+            jobs,
+            pipeline,
+            names,
+            status,
+            failure_reasons,
+        } => {
+            retry_job(
+                &client,
+                &project().await?,
+                jobs,
+                pipeline,
+                names,
+                status,
+                failure_reasons,
+            )
+            .await?;
+        }
+        Command::GetArtifact {
+            job,
+            names,
+            output_dir,
+            force,
+            ..
+        } => {
+            get_artifact(&client, &project().await?, job, names, output_dir, force).await?;
         }
-        Command::GetArtifact { job, name } => {
-            get_artifact(&creds, &project, job, name).await?;
+        Command::Artifact(ArtifactCommand::List { job }) => {
+            list_artifacts(&client, &project().await?, job, &output).await?;
         }
         Command::JobHistory {
             name,
@@ -259,24 +846,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             source,
             rref,
         } => {
-            let max_age = parse(&max_age)?.as_secs() as isize;
-            job_history(&creds, &project, &name, max_age, source, rref).await?;
+            let max_age = parse_max_age(&max_age)?;
+            job_history(
+                &client,
+                &project().await?,
+                &name,
+                max_age,
+                source,
+                rref,
+                &output,
+            )
+            .await?;
         }
         Command::ListProjects {} => {
-            list_projects(&creds).await?;
+            list_projects(&client, &output).await?;
         }
         Command::ListRunners { max_age } => {
-            let max_age = parse(&max_age)?.as_secs() as isize;
-            list_runners(&creds, max_age).await?;
+            let max_age = parse_max_age(&max_age)?;
+            list_runners(&client, max_age, &output).await?;
         }
         Command::ListPipelines {
             max_age,
             source,
             rref,
         } => {
-            let max_age = parse(&max_age)?.as_secs() as isize;
-            println!("max_age {}", max_age);
-            list_pipelines(&creds, &project, max_age, source, rref).await?;
+            let max_age = parse_max_age(&max_age)?;
+            list_pipelines(&client, &project().await?, max_age, source, rref, &output).await?;
         }
     }
 
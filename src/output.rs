@@ -0,0 +1,270 @@
+use std::cmp::Ordering;
+
+use clap::ValueEnum;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+
+use crate::error::GlcError;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable table
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+}
+
+type Render<'a, T, R> = Box<dyn Fn(&T) -> R + 'a>;
+
+/// One column of a tabular view. `value` is the plain text used for
+/// CSV/TSV and for sorting; `cell` optionally decorates it for the
+/// human table.
+pub struct Column<'a, T> {
+    pub title: &'static str,
+    key: Option<&'static str>,
+    hidden: bool,
+    value: Render<'a, T, String>,
+    cell: Option<Render<'a, T, Cell>>,
+}
+
+impl<'a, T> Column<'a, T> {
+    pub fn new(title: &'static str, value: impl Fn(&T) -> String + 'a) -> Self {
+        Column {
+            title,
+            key: None,
+            hidden: false,
+            value: Box::new(value),
+            cell: None,
+        }
+    }
+
+    /// Use a different rendering (colours, emoji, alignment) in tables
+    pub fn human(mut self, cell: impl Fn(&T) -> Cell + 'a) -> Self {
+        self.cell = Some(Box::new(cell));
+        self
+    }
+
+    /// Extra name for `--columns`/`--sort-by`, besides the title
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Only shown when asked for with `--columns`
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+
+    // Titles are matched by their lowercase, dash separated form,
+    // e.g. "Last activity" is "last-activity"
+    fn name(&self) -> String {
+        self.title
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        name.eq_ignore_ascii_case(&self.name())
+            || self.key.is_some_and(|k| name.eq_ignore_ascii_case(k))
+    }
+
+    fn cell(&self, record: &T) -> Cell {
+        match &self.cell {
+            Some(cell) => cell(record),
+            None => Cell::new(&(self.value)(record)),
+        }
+    }
+}
+
+/// Shows `titles`, in the order given, and hides the other columns.
+pub fn select<'a, T>(mut columns: Vec<Column<'a, T>>, titles: &[&str]) -> Vec<Column<'a, T>> {
+    let mut selected: Vec<Column<T>> = titles
+        .iter()
+        .filter_map(|t| {
+            let idx = columns.iter().position(|c| c.title == *t)?;
+            Some(columns.remove(idx))
+        })
+        .collect();
+    selected.extend(columns.into_iter().map(Column::hidden));
+    selected
+}
+
+/// Renders command results the way `--output`, `--columns` and
+/// `--sort-by` asked for.
+pub struct Output {
+    pub format: OutputFormat,
+    /// Columns to show, from `--columns` or the config file
+    pub columns: Option<Vec<String>>,
+    /// Column names to sort by, descending if prefixed with `-`
+    pub sort_by: Vec<String>,
+}
+
+impl Output {
+    pub fn is_table(&self) -> bool {
+        self.format == OutputFormat::Table
+    }
+
+    /// Prints `records`: serialized as a whole for JSON and YAML, one
+    /// line per record through `columns` for CSV, TSV and tables.
+    pub fn print<T: Serialize>(
+        &self,
+        records: &[T],
+        columns: &[Column<T>],
+    ) -> Result<(), GlcError> {
+        let records = self.sorted(records, columns)?;
+        let visible = self.visible(columns)?;
+
+        match self.format {
+            OutputFormat::Table => table(&records, &visible).printstd(),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&records)?),
+            OutputFormat::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(&records).map_err(|e| GlcError::Other(e.into()))?
+            ),
+            OutputFormat::Csv => print_delimited(&records, &visible, ',', csv_escape),
+            OutputFormat::Tsv => print_delimited(&records, &visible, '\t', tsv_escape),
+        }
+        Ok(())
+    }
+
+    /// Like `print`, with a `total` row kept at the bottom of the table
+    /// whatever the sort order. Other formats leave it out, as it isn't
+    /// one of the records.
+    pub fn print_with_total<T: Serialize>(
+        &self,
+        records: &[T],
+        total: &T,
+        columns: &[Column<T>],
+    ) -> Result<(), GlcError> {
+        if !self.is_table() {
+            return self.print(records, columns);
+        }
+        let mut records = self.sorted(records, columns)?;
+        records.push(total);
+        table(&records, &self.visible(columns)?).printstd();
+        Ok(())
+    }
+
+    /// Serializes `value` as a whole for JSON and YAML. Returns false,
+    /// printing nothing, for the tabular formats.
+    pub fn print_document<T: Serialize>(&self, value: &T) -> Result<bool, GlcError> {
+        match self.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+            OutputFormat::Yaml => print!(
+                "{}",
+                serde_yaml::to_string(value).map_err(|e| GlcError::Other(e.into()))?
+            ),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn visible<'c, 'a, T>(
+        &self,
+        columns: &'c [Column<'a, T>],
+    ) -> Result<Vec<&'c Column<'a, T>>, GlcError> {
+        match &self.columns {
+            Some(names) => names.iter().map(|n| find_column(columns, n)).collect(),
+            None => Ok(columns.iter().filter(|c| !c.hidden).collect()),
+        }
+    }
+
+    fn sorted<'r, T>(
+        &self,
+        records: &'r [T],
+        columns: &[Column<T>],
+    ) -> Result<Vec<&'r T>, GlcError> {
+        let keys = self
+            .sort_by
+            .iter()
+            .map(|s| match s.strip_prefix('-') {
+                Some(name) => Ok((find_column(columns, name)?, true)),
+                None => Ok((find_column(columns, s)?, false)),
+            })
+            .collect::<Result<Vec<_>, GlcError>>()?;
+
+        let mut records: Vec<&T> = records.iter().collect();
+        // Stable, so records keep their natural order within equal keys
+        records.sort_by(|a, b| {
+            keys.iter()
+                .map(|(column, descending)| {
+                    let order = compare_values(&(column.value)(a), &(column.value)(b));
+                    if *descending {
+                        order.reverse()
+                    } else {
+                        order
+                    }
+                })
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(records)
+    }
+}
+
+fn find_column<'c, 'a, T>(
+    columns: &'c [Column<'a, T>],
+    name: &str,
+) -> Result<&'c Column<'a, T>, GlcError> {
+    columns.iter().find(|c| c.matches(name)).ok_or_else(|| {
+        let available: Vec<String> = columns.iter().map(|c| c.name()).collect();
+        GlcError::Config(format!(
+            "unknown column {:?}, available: {}",
+            name,
+            available.join(", ")
+        ))
+    })
+}
+
+// Numbers sort numerically, anything else as text
+fn compare_values(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn table<T>(records: &[&T], columns: &[&Column<T>]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(columns.iter().map(|c| Cell::new(c.title)).collect());
+    for record in records {
+        table.add_row(Row::new(columns.iter().map(|c| c.cell(record)).collect()));
+    }
+    table
+}
+
+fn print_delimited<T>(
+    records: &[&T],
+    columns: &[&Column<T>],
+    separator: char,
+    escape: fn(&str) -> String,
+) {
+    let separator = separator.to_string();
+    let titles: Vec<String> = columns.iter().map(|c| escape(c.title)).collect();
+    println!("{}", titles.join(&separator));
+    for record in records {
+        let fields: Vec<String> = columns.iter().map(|c| escape(&(c.value)(record))).collect();
+        println!("{}", fields.join(&separator));
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// TSV has no quoting, so field separators are flattened to spaces
+fn tsv_escape(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::ready;
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use regex::Regex;
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{Method, Url};
+use serde::de::DeserializeOwned;
+
+use crate::client::{parse_json, GitlabClient};
+use crate::error::check_status;
+
+/// How a list endpoint is walked.
+#[derive(Clone, Copy)]
+pub enum Pagination {
+    /// `page=N` pagination, supported by every list endpoint. Follows the
+    /// `Link` header when present and falls back to `x-next-page`.
+    Offset,
+    /// Cursor based pagination, newest first by `order_by`. Only some
+    /// endpoints support it, but it stays fast on deep pages.
+    Keyset { order_by: &'static str },
+}
+
+/// Streams every item of a GitLab list endpoint. Pages are only fetched
+/// as the stream is polled, so dropping it (or ending it early with
+/// `newer_than`) stops further requests.
+pub fn paginate<T>(
+    client: &GitlabClient,
+    mut url: Url,
+    pagination: Pagination,
+) -> BoxStream<'static, Result<T>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    if let Pagination::Keyset { order_by } = pagination {
+        url.query_pairs_mut()
+            .append_pair("pagination", "keyset")
+            .append_pair("order_by", order_by)
+            .append_pair("sort", "desc");
+    }
+    let client = client.clone();
+
+    stream::try_unfold(Some(url), move |next| {
+        let client = client.clone();
+        async move {
+            let Some(url) = next else {
+                return Ok(None);
+            };
+            let response = client
+                .send(client.request(Method::GET, url.clone()))
+                .await?;
+            let response = check_status(response).await?;
+            let next = next_page(&url, response.headers());
+            let items: Vec<T> = parse_json(response).await?;
+            // An empty page is the end, whatever the headers claim
+            let next = if items.is_empty() { None } else { next };
+
+            Ok::<_, anyhow::Error>(Some((stream::iter(items.into_iter().map(Ok)), next)))
+        }
+    })
+    .try_flatten()
+    .boxed()
+}
+
+/// Ends a newest-first stream at the first item older than `max_age`
+/// seconds, so no further pages are requested.
+pub fn newer_than<T, S>(
+    items: S,
+    max_age: isize,
+    created_at: fn(&T) -> DateTime<Utc>,
+) -> impl Stream<Item = Result<T>>
+where
+    S: Stream<Item = Result<T>>,
+{
+    items.try_take_while(move |item| {
+        ready(Ok(
+            (Utc::now() - created_at(item)).num_seconds() as isize <= max_age
+        ))
+    })
+}
+
+fn next_page(url: &Url, headers: &HeaderMap) -> Option<Url> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(link) = header(LINK.as_str()) {
+        return parse_next_link(link).and_then(|next| Url::parse(&next).ok());
+    }
+
+    // Some GitLab versions leave out the Link header, e.g. on single
+    // page results, but offset pagination always sets x-next-page
+    let page = header("x-next-page").filter(|p| !p.is_empty())?;
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "page")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    let mut next = url.clone();
+    next.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("page", page);
+    Some(next)
+}
+
+fn parse_next_link(link_header: &str) -> Option<String> {
+    let re = Regex::new(r#"<([^>]*)>;\s*rel="([^"]*)""#).unwrap();
+
+    link_header
+        .split(',')
+        .filter_map(|link| re.captures(link))
+        .find(|cap| &cap[2] == "next")
+        .map(|cap| cap[1].to_string())
+}
@@ -1,16 +1,16 @@
-use std::collections::HashMap;
 use std::io::{self, Write};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
-use regex::Regex;
-use reqwest::header::LINK;
-use reqwest::Url;
-use serde_derive::Deserialize;
+use futures::TryStreamExt;
+use reqwest::{Method, Url};
+use serde_derive::{Deserialize, Serialize};
 
-use crate::credentials::Credentials;
+use crate::client::{parse_json, GitlabClient};
+use crate::error::check_status;
+use crate::pagination::{newer_than, paginate, Pagination};
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Pipeline {
     pub id: u32,
     pub project_id: u32,
@@ -26,92 +26,143 @@ pub struct Pipeline {
     pub web_url: String,
 }
 
-// Returns number of seconds since the rfc3339 timestamp
-fn seconds_ago(datetime: &str) -> isize {
-    let timestamp: chrono::DateTime<Utc> = DateTime::parse_from_rfc3339(datetime)
-        .expect("Failed to parse timestamp")
-        .into();
-    let now = Utc::now();
+pub async fn get_pipeline(client: &GitlabClient, project: &str, id: usize) -> Result<Pipeline> {
+    client
+        .get_json(&format!("projects/{}/pipelines/{}", project, id))
+        .await
+}
+
+/// Newest pipeline for a branch/tag and/or commit, if there is one yet.
+pub async fn latest_pipeline(
+    client: &GitlabClient,
+    project: &str,
+    rref: Option<&str>,
+    sha: Option<&str>,
+) -> Result<Option<Pipeline>> {
+    let mut url = client.api_url(&format!("projects/{}/pipelines", project))?;
+    url.query_pairs_mut()
+        .append_pair("per_page", "1")
+        .append_pair("order_by", "id")
+        .append_pair("sort", "desc");
+    if let Some(rref) = rref {
+        url.query_pairs_mut().append_pair("ref", rref);
+    }
+    if let Some(sha) = sha {
+        url.query_pairs_mut().append_pair("sha", sha);
+    }
+    let response = check_status(client.send(client.request(Method::GET, url)).await?).await?;
+    let pipelines: Vec<Pipeline> = parse_json(response).await?;
+    Ok(pipelines.into_iter().next())
+}
+
+/// Which side of a point in time pipelines were last updated on
+enum Updated {
+    After(DateTime<Utc>),
+    Before(DateTime<Utc>),
+}
 
-    (now - timestamp).num_seconds() as isize
+// The pipelines endpoint with the filters both searches share, announcing
+// the search on stderr
+fn pipelines_url(
+    client: &GitlabClient,
+    project: &str,
+    updated: Updated,
+    source: Option<&str>,
+    rref: Option<&str>,
+    status: Option<&str>,
+) -> Result<Url> {
+    let (key, word, time) = match updated {
+        Updated::After(time) => ("updated_after", "after", time),
+        Updated::Before(time) => ("updated_before", "before", time),
+    };
+    let mut url = client.api_url(&format!("projects/{}/pipelines", project))?;
+    url.query_pairs_mut()
+        .append_pair("per_page", "100")
+        .append_pair(key, &time.to_rfc3339());
+    for (key, value) in [("source", source), ("ref", rref), ("status", status)] {
+        if let Some(value) = value {
+            url.query_pairs_mut().append_pair(key, value);
+        }
+    }
+
+    // Progress goes to stderr to keep stdout clean for --output
+    eprintln!(
+        "Searching for pipelines updated {} {} matching Ref: {} Source: {} Status: {}",
+        word,
+        time.format("%Y-%m-%d %H:%M"),
+        rref.unwrap_or("any"),
+        source.unwrap_or("any"),
+        status.unwrap_or("any")
+    );
+    Ok(url)
+}
+
+// Clamp so that "no limit" (isize::MAX) doesn't overflow the date math
+fn seconds_ago(seconds: isize) -> DateTime<Utc> {
+    Utc::now() - Duration::seconds(seconds.min(i32::MAX as isize) as i64)
 }
 
 pub async fn get_pipelines(
-    creds: &Credentials,
+    client: &GitlabClient,
     project: &str,
     max_age: isize,
     source: Option<String>,
     rref: Option<String>,
 ) -> Result<Vec<Pipeline>, anyhow::Error> {
-    let url = format!(
-        "{}/api/v4/projects/{}/pipelines?per_page=100&updated_after={}",
-        creds.url,
+    let url = pipelines_url(
+        client,
         project,
-        (Utc::now() - Duration::seconds(max_age as i64)).to_rfc3339()
-    );
-    let url = Url::parse(&url)?;
-    let mut pipelines: Vec<Pipeline> = Vec::new();
-    let client = reqwest::Client::new();
-    let mut next_url: Option<String> = Some(url.to_string());
-    let mut stdout = io::stdout();
-
-    println!(
-        "Searching for pipelines matching Ref: {} Source: {}",
-        rref.as_ref().unwrap_or(&"any".to_string()),
-        source.as_ref().unwrap_or(&"any".to_string())
-    );
-    print!("Pipelines: ");
-    stdout.flush().unwrap();
-
-    while let Some(url) = next_url {
-        let response = client.get(url).bearer_auth(&creds.token).send().await?;
-        let link_header = response
-            .headers()
-            .get(LINK)
-            .ok_or(anyhow!("Missing Link header"))?
-            .to_str()?;
-
-        print!(".");
-        stdout.flush().unwrap();
-        next_url = parse_next_page(link_header);
-
-        let mut pipelines_page: Vec<Pipeline> = response.json().await?;
-        let res_max_age = pipelines_page
-            .iter()
-            .map(|p| seconds_ago(p.created_at.as_ref().unwrap()))
-            .max()
-            .unwrap();
-        pipelines_page.retain(|p| seconds_ago(p.created_at.as_ref().unwrap()) <= max_age);
-        if let Some(src) = source.clone() {
-            pipelines_page.retain(|p| p.source == src);
-        }
-        if let Some(rref) = rref.clone() {
-            pipelines_page.retain(|p| p.rref == rref);
-        }
-        if res_max_age > max_age {
-            next_url = None;
-        }
-        pipelines.append(&mut pipelines_page);
-    }
-    println!(" {} matched", pipelines.len());
+        Updated::After(seconds_ago(max_age)),
+        source.as_deref(),
+        rref.as_deref(),
+        None,
+    )?;
+    eprint!("Pipelines: ");
+    io::stderr().flush().unwrap();
+
+    let pipelines: Vec<Pipeline> = newer_than(
+        paginate(client, url, Pagination::Offset),
+        max_age,
+        created_at,
+    )
+    .try_collect()
+    .await?;
+    eprintln!(" {} matched", pipelines.len());
+
+    Ok(pipelines.into_iter().rev().collect())
+}
+
+/// Pipelines last updated more than `older_than` seconds ago, oldest
+/// first. The other filters are the same as `get_pipelines`, plus status.
+pub async fn get_old_pipelines(
+    client: &GitlabClient,
+    project: &str,
+    older_than: isize,
+    source: Option<String>,
+    rref: Option<String>,
+    status: Option<String>,
+) -> Result<Vec<Pipeline>> {
+    let url = pipelines_url(
+        client,
+        project,
+        Updated::Before(seconds_ago(older_than)),
+        source.as_deref(),
+        rref.as_deref(),
+        status.as_deref(),
+    )?;
+
+    let pipelines: Vec<Pipeline> = paginate(client, url, Pagination::Offset)
+        .try_collect()
+        .await?;
+    eprintln!("Pipelines: {} matched", pipelines.len());
 
     Ok(pipelines.into_iter().rev().collect())
 }
 
-fn parse_next_page(link_header: &str) -> Option<String> {
-    let links: HashMap<String, String> = link_header
-        .split(',')
-        .map(|line| {
-            let re = Regex::new(r#"<([^>]*)>;\s*rel="([^"]*)""#).unwrap();
-
-            re.captures(line)
-                .map(|cap| {
-                    let url = &cap[1];
-                    let rel = &cap[2];
-                    (rel.into(), url.into())
-                })
-                .unwrap()
-        })
-        .collect();
-    links.get("next").cloned()
+pub fn created_at(pipeline: &Pipeline) -> DateTime<Utc> {
+    pipeline
+        .created_at
+        .as_deref()
+        .and_then(|c| DateTime::parse_from_rfc3339(c).ok())
+        .map_or(DateTime::<Utc>::MIN_UTC, Into::into)
 }
@@ -1,10 +1,22 @@
-use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
 
-#[derive(Deserialize, Clone, Debug)]
+use anyhow::Result;
+use reqwest::Url;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::client::GitlabClient;
+use crate::config::cache_dir;
+use crate::credentials::Credentials;
+use crate::error::GlcError;
+use crate::git::{project_path, remote_project_path};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Project {
     pub avatar_url: Option<String>,
     pub created_at: String,
-    pub default_branch: String,
+    pub default_branch: Option<String>,
     pub description: Option<String>,
     pub forks_count: usize,
     pub http_url_to_repo: String,
@@ -15,10 +27,115 @@ pub struct Project {
     //pub namespace: Option<Namespace>,
     pub path: String,
     pub path_with_namespace: String,
-    pub readme_url: String,
+    pub readme_url: Option<String>,
     pub ssh_url_to_repo: String,
     pub star_count: usize,
+    // Deprecated in favour of topics, newer servers leave it out
+    #[serde(default)]
     pub tag_list: Vec<String>,
+    #[serde(default)]
     pub topics: Vec<String>,
     pub web_url: String,
 }
+
+pub async fn get_project(client: &GitlabClient, path: &str) -> Result<Project> {
+    client
+        .get_json(&format!("projects/{}", urlencoding::encode(path)))
+        .await
+}
+
+/// Picks the project to work on. `-P` (or `GITLAB_PROJECT`) wins, then
+/// the git remote of the current directory if it lives on this GitLab,
+/// then the profile's default project. Whichever it is gets turned into
+/// a numeric project ID.
+pub async fn resolve_project(
+    client: &GitlabClient,
+    creds: &Credentials,
+    project: Option<String>,
+) -> Result<String, GlcError> {
+    let gitlab_url = Url::parse(&creds.url)
+        .map_err(|e| GlcError::Config(format!("invalid GitLab URL {:?}: {}", creds.url, e)))?;
+
+    let project = project
+        .or_else(|| remote_project_path(&gitlab_url))
+        .or_else(|| creds.project.clone())
+        .ok_or_else(|| {
+            GlcError::Config(
+                "no project given, use -P, set GITLAB_PROJECT or run from a clone of the project"
+                    .to_string(),
+            )
+        })?;
+
+    project_id(client, &gitlab_url, project.trim()).await
+}
+
+// Accepts a numeric ID, a `group/sub/project` path or a web URL
+async fn project_id(
+    client: &GitlabClient,
+    gitlab_url: &Url,
+    project: &str,
+) -> Result<String, GlcError> {
+    if project.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(project.to_string());
+    }
+
+    let path = if project.contains("://") {
+        project_path(project, gitlab_url).ok_or_else(|| {
+            GlcError::Config(format!("{} is not a project on {}", project, gitlab_url))
+        })?
+    } else {
+        project.trim_matches('/').to_string()
+    };
+
+    let mut cache = ProjectCache::load();
+    if let Some(id) = cache.get(gitlab_url, &path) {
+        return Ok(id.to_string());
+    }
+
+    let id = get_project(client, &path).await?.id;
+    cache.insert(gitlab_url, &path, id);
+    // The cache only saves a round trip, so failing to write it is fine
+    let _ = cache.save();
+
+    Ok(id.to_string())
+}
+
+/// Project path to ID mappings, per GitLab instance, kept in
+/// `$XDG_CACHE_HOME/glc/projects.yaml` (or `~/.cache/glc/projects.yaml`).
+#[derive(Serialize, Deserialize, Default)]
+struct ProjectCache {
+    instances: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl ProjectCache {
+    fn path() -> Option<PathBuf> {
+        Some(cache_dir()?.join("projects.yaml"))
+    }
+
+    fn load() -> ProjectCache {
+        Self::path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_yaml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = Self::path().ok_or(std::io::ErrorKind::NotFound)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_yaml::to_string(self).map_err(std::io::Error::other)?;
+        fs::write(path, contents)
+    }
+
+    fn get(&self, gitlab_url: &Url, path: &str) -> Option<usize> {
+        self.instances.get(gitlab_url.as_str())?.get(path).copied()
+    }
+
+    fn insert(&mut self, gitlab_url: &Url, path: &str, id: usize) {
+        self.instances
+            .entry(gitlab_url.to_string())
+            .or_default()
+            .insert(path.to_string(), id);
+    }
+}
@@ -0,0 +1,12 @@
+use std::io::{self, BufRead, Write};
+
+/// Asks a yes/no question on the terminal. Anything but a yes, including
+/// no input at all, is a no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
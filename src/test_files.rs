@@ -0,0 +1,342 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use roxmltree::{Document, Node};
+use serde_derive::Deserialize;
+
+use crate::commands::test_report::{TestCase, TestSuite};
+
+/// Parses a test result file, telling the format from its contents:
+/// JUnit XML, xUnit XML, or the JSON lines of libtest's
+/// `--format json`.
+pub fn parse_test_file(file_name: &str, contents: &str) -> Result<Vec<TestSuite>> {
+    // Suites that don't name themselves are named after the file
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map_or(file_name.to_string(), |s| s.to_string_lossy().into_owned());
+
+    // .NET tools write XML with a byte order mark in front
+    if contents
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('<')
+    {
+        let doc = Document::parse(contents).map_err(|e| anyhow!("{}: {}", file_name, e))?;
+        let root = doc.root_element();
+        match root.tag_name().name() {
+            "testsuites" | "testsuite" => Ok(parse_junit(root, &stem)),
+            "assemblies" | "assembly" => Ok(parse_xunit(root, &stem)),
+            tag => Err(anyhow!(
+                "{}: unknown test report format <{}>",
+                file_name,
+                tag
+            )),
+        }
+    } else {
+        parse_libtest(contents, &stem).map_err(|e| anyhow!("{}: {}", file_name, e))
+    }
+}
+
+fn child<'a, 'i>(node: Node<'a, 'i>, tag: &str) -> Option<Node<'a, 'i>> {
+    node.children().find(|c| c.has_tag_name(tag))
+}
+
+// Text of an element, falling back to its message attribute
+fn text_of(node: Node) -> Option<String> {
+    node.text()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .or_else(|| node.attribute("message"))
+        .map(str::to_string)
+}
+
+fn time_of(node: Node) -> f64 {
+    node.attribute("time")
+        .and_then(|t| t.parse().ok())
+        .unwrap_or_default()
+}
+
+// <testsuites><testsuite name><testcase name classname time>, with
+// <failure>, <error> or <skipped> in failed or skipped cases. Suites
+// may be nested.
+fn parse_junit(root: Node, stem: &str) -> Vec<TestSuite> {
+    root.descendants()
+        .filter(|n| n.has_tag_name("testsuite"))
+        .filter_map(|suite| {
+            let cases: Vec<TestCase> = suite
+                .children()
+                .filter(|n| n.has_tag_name("testcase"))
+                .map(|case| {
+                    let failure = child(case, "failure");
+                    let error = child(case, "error");
+                    let status = if failure.is_some() {
+                        "failed"
+                    } else if error.is_some() {
+                        "error"
+                    } else if child(case, "skipped").is_some() {
+                        "skipped"
+                    } else {
+                        "success"
+                    };
+                    TestCase {
+                        status: status.to_string(),
+                        name: case.attribute("name").unwrap_or_default().to_string(),
+                        classname: case.attribute("classname").unwrap_or_default().to_string(),
+                        execution_time: time_of(case),
+                        system_output: child(case, "system-out")
+                            .or_else(|| child(case, "system-err"))
+                            .and_then(text_of),
+                        stack_trace: failure.or(error).and_then(text_of),
+                    }
+                })
+                .collect();
+            // Suites only holding other suites
+            if cases.is_empty() {
+                return None;
+            }
+            let name = suite.attribute("name").unwrap_or(stem);
+            Some(TestSuite::new(name, cases))
+        })
+        .collect()
+}
+
+// xUnit.net: <assemblies><assembly><collection name><test name type
+// method time result="Pass|Fail|Skip">, with <failure><message/>
+// <stack-trace/></failure> and <output/>. Version 1 files have <class>
+// instead of <collection>.
+fn parse_xunit(root: Node, stem: &str) -> Vec<TestSuite> {
+    root.descendants()
+        .filter(|n| n.has_tag_name("collection") || n.has_tag_name("class"))
+        .map(|group| {
+            let cases: Vec<TestCase> = group
+                .children()
+                .filter(|n| n.has_tag_name("test"))
+                .map(|test| {
+                    let status = match test.attribute("result") {
+                        Some("Pass") => "success",
+                        Some("Fail") => "failed",
+                        Some("Skip") => "skipped",
+                        _ => "error",
+                    };
+                    let failure = child(test, "failure");
+                    let stack_trace = failure.map(|f| {
+                        [child(f, "message"), child(f, "stack-trace")]
+                            .into_iter()
+                            .flatten()
+                            .filter_map(text_of)
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    });
+                    TestCase {
+                        status: status.to_string(),
+                        name: test
+                            .attribute("method")
+                            .or(test.attribute("name"))
+                            .unwrap_or_default()
+                            .to_string(),
+                        classname: test.attribute("type").unwrap_or_default().to_string(),
+                        execution_time: time_of(test),
+                        system_output: child(test, "output").and_then(text_of),
+                        stack_trace,
+                    }
+                })
+                .collect();
+            let name = group.attribute("name").unwrap_or(stem);
+            TestSuite::new(name, cases)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct LibtestEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    exec_time: Option<f64>,
+    stdout: Option<String>,
+}
+
+// One JSON object per line, of which the finished tests are of interest:
+// {"type":"test","event":"failed","name":"mod::test","exec_time":0.1,"stdout":"..."}
+fn parse_libtest(contents: &str, stem: &str) -> Result<Vec<TestSuite>> {
+    let mut cases = Vec::new();
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        let event: LibtestEvent = serde_json::from_str(line)?;
+        if event.kind != "test" {
+            continue;
+        }
+        // "timeout" only warns that a test is running long, its result
+        // still follows
+        let status = match event.event.as_str() {
+            "ok" => "success",
+            "failed" => "failed",
+            "ignored" => "skipped",
+            _ => continue,
+        };
+        let path = event.name.unwrap_or_default();
+        let (classname, name) = path.rsplit_once("::").unwrap_or(("", &path));
+        cases.push(TestCase {
+            status: status.to_string(),
+            name: name.to_string(),
+            classname: classname.to_string(),
+            execution_time: event.exec_time.unwrap_or_default(),
+            system_output: None,
+            stack_trace: event.stdout,
+        });
+    }
+    Ok(vec![TestSuite::new(stem, cases)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(suite: &TestSuite) -> Vec<(&str, &str)> {
+        suite
+            .test_cases
+            .iter()
+            .map(|c| (c.name.as_str(), c.status.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_junit() {
+        let xml = r#"<?xml version="1.0"?>
+<testsuites>
+  <testsuite name="parser">
+    <testcase name="parses_empty" classname="p.Parser" time="0.5"/>
+    <testcase name="parses_bad" classname="p.Parser" time="1.5">
+      <failure message="expected 1">assertion failed at parser.rs:10</failure>
+      <system-out>debug output</system-out>
+    </testcase>
+    <testcase name="crashes" classname="p.Parser"><error message="segfault"/></testcase>
+    <testcase name="todo" classname="p.Parser"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+        let suites = parse_test_file("reports/junit.xml", xml).unwrap();
+        assert_eq!(suites.len(), 1);
+        let suite = &suites[0];
+        assert_eq!(suite.name, "parser");
+        assert_eq!(
+            statuses(suite),
+            [
+                ("parses_empty", "success"),
+                ("parses_bad", "failed"),
+                ("crashes", "error"),
+                ("todo", "skipped")
+            ]
+        );
+        assert_eq!(
+            (
+                suite.total_count,
+                suite.failed_count,
+                suite.error_count,
+                suite.skipped_count
+            ),
+            (4, 1, 1, 1)
+        );
+        assert_eq!(suite.total_time, 2.0);
+
+        let bad = &suite.test_cases[1];
+        assert_eq!(bad.classname, "p.Parser");
+        assert_eq!(
+            bad.stack_trace.as_deref(),
+            Some("assertion failed at parser.rs:10")
+        );
+        assert_eq!(bad.system_output.as_deref(), Some("debug output"));
+        // Falls back to the message attribute
+        assert_eq!(suite.test_cases[2].stack_trace.as_deref(), Some("segfault"));
+    }
+
+    #[test]
+    fn parses_junit_with_bom() {
+        let xml = "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                   <testsuites><testsuite name=\"net\">\
+                   <testcase name=\"a\" time=\"0.1\"/>\
+                   <testcase name=\"b\"><failure message=\"nope\"/></testcase>\
+                   </testsuite></testsuites>";
+        let suites = parse_test_file("TestResults/junit.xml", xml).unwrap();
+        assert_eq!(suites[0].name, "net");
+        assert_eq!(statuses(&suites[0]), [("a", "success"), ("b", "failed")]);
+    }
+
+    #[test]
+    fn names_bare_junit_suites_after_the_file() {
+        let xml = r#"<testsuite><testcase name="a"/></testsuite>"#;
+        let suites = parse_test_file("out/results.xml", xml).unwrap();
+        assert_eq!(suites[0].name, "results");
+    }
+
+    #[test]
+    fn parses_xunit() {
+        let xml = r#"<assemblies>
+  <assembly name="Api.Tests.dll">
+    <collection name="Api tests">
+      <test name="Api.Get" type="Api" method="Get" time="0.25" result="Pass"/>
+      <test name="Api.Post" type="Api" method="Post" time="1.5" result="Fail">
+        <failure>
+          <message>500 returned</message>
+          <stack-trace>at Api.Post()</stack-trace>
+        </failure>
+      </test>
+      <test name="Api.Put" type="Api" method="Put" time="0" result="Skip"/>
+    </collection>
+  </assembly>
+</assemblies>"#;
+        let suites = parse_test_file("xunit.xml", xml).unwrap();
+        assert_eq!(suites.len(), 1);
+        let suite = &suites[0];
+        assert_eq!(suite.name, "Api tests");
+        assert_eq!(
+            statuses(suite),
+            [("Get", "success"), ("Post", "failed"), ("Put", "skipped")]
+        );
+        assert_eq!(suite.test_cases[1].classname, "Api");
+        assert_eq!(
+            suite.test_cases[1].stack_trace.as_deref(),
+            Some("500 returned\nat Api.Post()")
+        );
+    }
+
+    #[test]
+    fn parses_libtest() {
+        let json = r#"{ "type": "suite", "event": "started", "test_count": 4 }
+{ "type": "test", "event": "started", "name": "util::tests::adds" }
+{ "type": "test", "event": "ok", "name": "util::tests::adds", "exec_time": 0.25 }
+{ "type": "test", "event": "started", "name": "util::tests::slow" }
+{ "type": "test", "event": "timeout", "name": "util::tests::slow" }
+{ "type": "test", "event": "ok", "name": "util::tests::slow", "exec_time": 61.0 }
+{ "type": "test", "event": "failed", "name": "util::tests::subs", "stdout": "panicked at 1 != 2" }
+{ "type": "test", "event": "ignored", "name": "top_level" }
+{ "type": "suite", "event": "failed", "passed": 2, "failed": 1, "ignored": 1 }
+"#;
+        let suites = parse_test_file("target/libtest.json", json).unwrap();
+        assert_eq!(suites.len(), 1);
+        let suite = &suites[0];
+        assert_eq!(suite.name, "libtest");
+        // The timeout warning isn't a result of its own
+        assert_eq!(
+            statuses(suite),
+            [
+                ("adds", "success"),
+                ("slow", "success"),
+                ("subs", "failed"),
+                ("top_level", "skipped")
+            ]
+        );
+        assert_eq!((suite.total_count, suite.failed_count), (4, 1));
+        assert_eq!(suite.test_cases[0].classname, "util::tests");
+        assert_eq!(suite.test_cases[3].classname, "");
+        assert_eq!(
+            suite.test_cases[2].stack_trace.as_deref(),
+            Some("panicked at 1 != 2")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert!(parse_test_file("report.xml", "<html></html>").is_err());
+        assert!(parse_test_file("report.txt", "all tests passed").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use reqwest::header::RANGE;
+use reqwest::{Method, StatusCode};
+
+use crate::client::GitlabClient;
+use crate::error::check_status;
+
+/// A job log that is read a piece at a time while the job runs. Every
+/// read asks only for the bytes past what has been seen so far and hands
+/// back the lines that were completed since the last read.
+pub struct Trace {
+    path: String,
+    offset: usize,
+    // Bytes after the last newline, kept until the line is complete
+    partial: Vec<u8>,
+}
+
+impl Trace {
+    pub fn new(project: &str, job: usize) -> Trace {
+        Trace {
+            path: format!("projects/{}/jobs/{}/trace", project, job),
+            offset: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Lines appended to the log since the last call.
+    pub async fn read(&mut self, client: &GitlabClient) -> Result<Vec<String>> {
+        let url = client.api_url(&self.path)?;
+        let request = client
+            .request(Method::GET, url)
+            .header(RANGE, format!("bytes={}-", self.offset));
+        let response = client.send(request).await?;
+        // Nothing was added since the last read
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(Vec::new());
+        }
+        let response = check_status(response).await?;
+        let partial_content = response.status() == StatusCode::PARTIAL_CONTENT;
+        let body = response.bytes().await?;
+
+        // Servers that ignore the Range header send the whole log again
+        let new = if partial_content {
+            &body[..]
+        } else {
+            body.get(self.offset..).unwrap_or_default()
+        };
+        self.offset += new.len();
+        self.partial.extend_from_slice(new);
+
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The last line, if the log doesn't end with a newline.
+    pub fn rest(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let rest = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial.clear();
+        Some(rest)
+    }
+}